@@ -0,0 +1,5212 @@
+use clap::parser::ValueSource;
+use clap::{CommandFactory, Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::net::IpAddr;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::{CrosstermBackend, TestBackend};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::bar::Set;
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{BarChart, Block, Borders, Clear, Paragraph, Sparkline, Tabs, Wrap};
+use ratatui::{Frame, Terminal};
+
+use dcgm_tui::{buffer_percentiles, format_bytes_with_unit, format_compact_value, format_metric_value, metric_kind, parse_metric_line, parse_percentile, pearson, watchface_label, Entity, MetricBuffer, MetricKind, CLOCK_FIELDS, DEFAULT_FIELDS, FIELD_DOCS};
+use dcgm_tui::discovery::{query_gpu_names, query_max_clocks, query_processes, query_total_memory, GpuProcess};
+use dcgm_tui::nvml::{NvmlReader, NVML_FIELDS};
+use dcgm_tui::logging::{append_annotation, open_log_file, spawn_logger_thread, spawn_ring_logger_thread, parse_delimiter, split_csv_line, LogDir, LogOptions, Logger, OutputFormat, Rotation, TimestampFormat};
+use dcgm_tui::recording::{read_frames, FrameWriter};
+use dcgm_tui::ringlog::{is_ring_log, read_ring_log, RingWriter};
+use dcgm_tui::screenshot::{export_png, DefaultColors};
+use dcgm_tui::topology::{query_topology, Link, Topology, NVLINK_LINK_BYTES_PER_SEC};
+
+/// GPU DCGM TUI Viewer
+#[derive(Parser)]
+#[command(after_help = "Query a running instance started with --socket /tmp/dcgmi.sock:\n  nc -U /tmp/dcgmi.sock | jq .")]
+struct Args {
+    /// Sampling interval in milliseconds
+    #[arg(short = 'i', long = "interval", default_value_t = 100)]
+    interval_ms: u64,
+
+    /// Adjust the interval at runtime to keep this process near --target-cpu, restarting
+    /// dcgmi (or the mock) whenever it changes
+    #[arg(long = "interval-auto", conflicts_with_all = ["replay", "input_file"])]
+    interval_auto: bool,
+
+    /// CPU usage of this process, in percent of one core, that --interval-auto aims for
+    #[arg(long = "target-cpu", default_value_t = 2.0, requires = "interval_auto")]
+    target_cpu: f64,
+
+    /// Shortest interval --interval-auto may pick, in milliseconds
+    #[arg(long = "min-interval", default_value_t = 50, requires = "interval_auto")]
+    min_interval: u64,
+
+    /// Longest interval --interval-auto may pick, in milliseconds
+    #[arg(long = "max-interval", default_value_t = 5000, requires = "interval_auto")]
+    max_interval: u64,
+
+    /// Maximum TUI redraws per second, independent of the sampling interval
+    #[arg(long = "max-fps", default_value_t = 10)]
+    max_fps: u64,
+
+    /// Show the average time each redraw takes in the footer
+    #[arg(long = "benchmark")]
+    benchmark: bool,
+
+    /// Debug overlay: chart the actual redraw rate in the top-right corner
+    #[arg(long = "fps-chart")]
+    fps_chart: bool,
+
+    /// Print the id, name, description and unit of every configured field and exit
+    #[arg(long = "field-doc")]
+    field_doc: bool,
+
+    /// Smoothing factor of the trend line drawn over each bar chart (0..1)
+    #[arg(long = "ema-alpha", default_value_t = 0.1)]
+    ema_alpha: f64,
+
+    /// Draw each chart as a moving average of the last N samples; logs and percentiles stay raw
+    #[arg(long = "smooth")]
+    smooth: Option<usize>,
+
+    /// Show "Collecting data..." instead of the charts for the first N seconds, or until
+    /// the history buffers are full if that comes sooner
+    #[arg(long = "silent-start", conflicts_with = "no_tui")]
+    silent_start: Option<u64>,
+
+    /// Grow the bars up from the bottom of each chart or hang them down from the top
+    #[arg(long = "graph-style", value_enum, default_value_t = GraphStyle::Up)]
+    graph_style: GraphStyle,
+
+    /// Draw every non-zero bar at least this percent of the chart's height, so tiny values
+    /// don't read as zero
+    #[arg(long = "min-bar-height")]
+    min_bar_height: Option<f64>,
+
+    /// Colour of bars raised by --min-bar-height, e.g. cyan or #00afff
+    #[arg(long = "min-bar-height-colour", requires = "min_bar_height", value_parser = parse_colour)]
+    min_bar_height_colour: Option<Color>,
+
+    /// Compute the stats panel's percentiles over only the last this many seconds
+    /// instead of the whole history
+    #[arg(long = "rolling-window")]
+    rolling_window: Option<u64>,
+
+    /// Quit automatically after this many seconds
+    #[arg(long = "duration")]
+    duration: Option<u64>,
+
+    /// Quit after this many parsed samples (across all GPUs)
+    #[arg(long = "sample-count")]
+    sample_count: Option<u64>,
+
+    /// Serve Prometheus metrics on this port at /metrics
+    #[arg(long = "prometheus-port")]
+    prometheus_port: Option<u16>,
+
+    /// Address the --prometheus-port endpoint listens on; use 0.0.0.0 for remote scrapers
+    #[arg(long = "prometheus-bind", default_value = "127.0.0.1", requires = "prometheus_port")]
+    prometheus_bind: IpAddr,
+
+    /// Respawn `dcgmi dmon` automatically if it exits
+    #[arg(long = "watchdog-restart")]
+    watchdog_restart: bool,
+
+    /// Give up after this many automatic restarts (unlimited by default)
+    #[arg(long = "watchdog-max-restarts", requires = "watchdog_restart")]
+    watchdog_max_restarts: Option<u32>,
+
+    /// CSV written by --log whose per-metric p50 is drawn as a reference line
+    #[arg(long = "baseline-file")]
+    baseline_file: Option<String>,
+
+    /// Percent the live p50 may differ from the baseline before the title turns yellow
+    #[arg(long = "baseline-tolerance", default_value_t = 10.0)]
+    baseline_tolerance: f64,
+
+    /// Quit once this process exits, e.g. the PID of a training job
+    #[arg(long = "watch-pid")]
+    watch_pid: Option<u32>,
+
+    /// Watch MIG instances: query a group (0 unless --group-id is given) and show each
+    /// `GPU n/m` instance under its GPU
+    #[arg(long = "mig")]
+    mig: bool,
+
+    /// Monitor every GPU in this DCGM group instead of the --gpus list
+    #[arg(long = "group-id", conflicts_with = "gpus")]
+    group_id: Option<u32>,
+
+    /// Read the metrics NVML offers (SMACT, DRAMA, PCIe, FB_USED, TEMP, POWER, clocks) straight
+    /// from the driver instead of dcgmi; also used automatically when dcgmi is not in PATH
+    #[arg(long = "nvml-fallback", conflicts_with_all = ["mock", "replay", "input_file", "group_id", "mig"])]
+    nvml_fallback: bool,
+
+    /// Show each GPU's model name from `dcgmi discovery -l`
+    #[arg(long = "gpu-name")]
+    gpu_name: bool,
+
+    /// Query `dcgmi topo` at startup so `t` can show the NVLink adjacency matrix
+    #[arg(long = "topo")]
+    topo: bool,
+
+    /// Start in the heatmap view (toggle with `h`)
+    #[arg(long = "heatmap")]
+    heatmap: bool,
+
+    /// Start in the metric correlation matrix (toggle with `C`)
+    #[arg(long = "correlation", conflicts_with = "heatmap")]
+    correlation: bool,
+
+    /// Every 5 s list each GPU's compute processes (`dcgmi pid`) in a panel under the charts (toggle with `r`)
+    #[arg(long = "show-procs", conflicts_with_all = ["replay", "input_file"])]
+    show_procs: bool,
+
+    /// Order of the metric rows (cycle at runtime with `o`)
+    #[arg(long = "sort", value_enum, default_value_t = SortMode::Definition)]
+    sort: SortMode,
+
+    /// Draw rows in this order instead of the field definition order, naming every metric
+    /// once, e.g. FB_USED,DRAMA,SMACT,...; logs keep the definition order
+    #[arg(long = "field-order", value_delimiter = ',')]
+    field_order: Vec<String>,
+
+    /// Only draw the N metrics with the highest latest value, re-ranked every frame; all are still logged
+    #[arg(long = "top-n")]
+    top_n: Option<usize>,
+
+    /// Draw one compact line per GPU instead of the charts, e.g. for a tmux status pane
+    #[arg(long = "watchface", conflicts_with = "no_tui")]
+    watchface: bool,
+
+    /// Gather related metrics (SM, Tensor, Memory, PCIe, NVLink, ...) under labelled dividers
+    #[arg(long = "metric-group")]
+    metric_group: bool,
+
+    /// Show one set of rows combining all GPUs instead of a tab per GPU
+    #[arg(long = "aggregate", value_enum)]
+    aggregate: Option<Aggregate>,
+
+    /// Chart A - B for two GPUs given as `A,B` to spot imbalances
+    #[arg(long = "diff-gpu", value_delimiter = ',', num_args = 1, conflicts_with = "aggregate")]
+    diff_gpu: Vec<usize>,
+
+    /// Draw every metric of two GPUs given as `A,B` side by side, with A - B in between
+    #[arg(long = "dual-gpu-compare", value_delimiter = ',', num_args = 1, conflicts_with_all = ["aggregate", "diff_gpu", "watchface"])]
+    dual_gpu_compare: Vec<usize>,
+
+    /// Latest value at which activity ratios turn yellow
+    #[arg(long = "warn-threshold", default_value_t = 0.7)]
+    warn_threshold: f64,
+
+    /// Latest value at which activity ratios turn red
+    #[arg(long = "critical-threshold", default_value_t = 0.9)]
+    critical_threshold: f64,
+
+    /// Per-metric `NAME:warn:critical` levels in raw units (bytes/s, MB, ...), e.g. SMACT:0.8:0.95,PCITX:8e9:1.2e10
+    #[arg(long = "metric-thresholds", value_delimiter = ',', value_parser = parse_metric_threshold)]
+    metric_thresholds: Vec<(String, Thresholds)>,
+
+    /// Colour temperature bars by `name:colour:low-high` bands in °C, e.g.
+    /// cold:blue:0-40,normal:green:40-75,warm:yellow:75-85,hot:red:85-120; overrides any thresholds on them
+    #[arg(long = "temp-ranges", value_delimiter = ',', value_parser = parse_temp_range)]
+    temp_ranges: Vec<TempRange>,
+
+    /// Fixed chart ceilings `NAME:max` in raw units, e.g. FB_USED:40960,SMACT:1.0; taller values are clipped
+    #[arg(long = "max-value", value_delimiter = ',', value_parser = parse_max_value)]
+    max_values: Vec<(String, f64)>,
+
+    /// Multiply raw values by `NAME:factor` before they are charted and logged, e.g. DRAMA:0.000001
+    #[arg(long = "metric-scale", value_delimiter = ',', value_parser = parse_metric_scale)]
+    metric_scales: Vec<(String, f64)>,
+
+    /// Unit `NAME:label` the stats panel shows instead of the field's own, e.g. DRAMA:MT/s
+    #[arg(long = "metric-unit", value_delimiter = ',', value_parser = parse_metric_unit)]
+    metric_units: Vec<(String, String)>,
+
+    /// Show the change since the previous sample instead of raw values, for every metric
+    #[arg(long = "delta")]
+    delta: bool,
+
+    /// Comma-separated metrics to show as deltas (implies --delta for just these)
+    #[arg(long = "delta-metrics", value_delimiter = ',')]
+    delta_metrics: Vec<String>,
+
+    /// Serve the latest values as JSON to clients of this Unix socket
+    #[arg(long = "socket")]
+    socket: Option<String>,
+
+    /// Colour palette
+    #[arg(long = "theme", value_enum, default_value_t = ThemeName::Dark)]
+    theme: ThemeName,
+
+    /// Path to log file (optional, CSV unless --output-format says otherwise)
+    #[arg(short = 'l', long = "log")]
+    log_file: Option<String>,
+
+    /// Log each GPU to its own `<dir>/gpu_<n>_<timestamp>.csv` (or .jsonl/.parquet) instead of one --log file
+    #[arg(long = "log-dir", conflicts_with = "log_file")]
+    log_dir: Option<String>,
+
+    /// Experiment name written to a `label` column of every CSV/JSONL row
+    #[arg(long = "label")]
+    label: Option<String>,
+
+    /// Identifier written to a `session_id` column of every CSV/JSONL row, so runs sharing one
+    /// log file can be told apart; a random UUID when not given
+    #[arg(long = "session-id")]
+    session_id: Option<String>,
+
+    /// Number of samples kept per metric
+    #[arg(long = "history-len", default_value_t = 100)]
+    history_len: usize,
+
+    /// Print min, mean, p50, p90, p99 and max of every metric to stdout on exit
+    #[arg(long = "headless-summary")]
+    headless_summary: bool,
+
+    /// Layout of the --headless-summary table
+    #[arg(long = "summary-format", value_enum, default_value_t = SummaryFormat::Tsv)]
+    summary_format: SummaryFormat,
+
+    /// Format of the --log file
+    #[arg(long = "output-format", value_enum, default_value_t = OutputFormat::Csv)]
+    output_format: OutputFormat,
+
+    /// Timestamp column of CSV/JSONL logs: unix_ms, unix_s, rfc3339 or a strftime pattern
+    #[arg(long = "csv-timestamp-format", value_parser = TimestampFormat::parse, default_value = "rfc3339")]
+    csv_timestamp_format: TimestampFormat,
+
+    /// Separator between CSV cells: comma, tab, semicolon or a single character
+    #[arg(long = "csv-delimiter", value_parser = parse_delimiter, default_value = "comma")]
+    csv_delimiter: char,
+
+    /// Also record samples into a fixed-size binary ring file that overwrites its oldest
+    /// records once full; --replay reads it back
+    #[arg(long = "samplelog-bin")]
+    samplelog_bin: Option<String>,
+
+    /// Records the --samplelog-bin ring holds before wrapping
+    #[arg(long = "samplelog-bin-records", default_value_t = 100_000, requires = "samplelog_bin")]
+    samplelog_bin_records: u64,
+
+    /// PNG written by `P` and, on exit, with the final frame (rendered off-screen with --no-tui)
+    #[arg(long = "export-png")]
+    export_png: Option<String>,
+
+    /// Save every drawn frame (cells and a timestamp) to this file, for bug reports and demos
+    #[arg(long = "record", conflicts_with = "no_tui")]
+    record: Option<String>,
+
+    /// Play back a --record file at its original timing instead of monitoring; Space pauses, q quits
+    #[arg(long = "play", conflicts_with = "record")]
+    play: Option<String>,
+
+    /// Every this many seconds, save a JSON snapshot of all buffers and percentiles to --checkpoint-dir
+    #[arg(long = "checkpoint")]
+    checkpoint: Option<u64>,
+
+    /// Directory --checkpoint writes `checkpoint_<unix_ts>.json` files to
+    #[arg(long = "checkpoint-dir", default_value = "checkpoints", requires = "checkpoint")]
+    checkpoint_dir: String,
+
+    /// Keep only this many of the newest checkpoints, deleting older ones
+    #[arg(long = "checkpoint-keep", requires = "checkpoint")]
+    checkpoint_keep: Option<usize>,
+
+    /// Rows buffered per Parquet row group with --output-format parquet
+    #[arg(long = "parquet-row-group-size", default_value_t = 1000)]
+    parquet_row_group_size: usize,
+
+    /// Rotate the CSV/JSONL log once it exceeds this many megabytes: it becomes
+    /// `<name>.1.csv` and a fresh file is started
+    #[arg(long = "rotate-log", requires = "log_file")]
+    rotate_log: Option<f64>,
+
+    /// Instead of every sample, log one CSV row per GPU every this many seconds with each
+    /// metric's mean, p50, p90, p99, min and max over the window
+    #[arg(long = "aggregate-period", conflicts_with = "rotate_log")]
+    aggregate_period: Option<u64>,
+
+    /// Rotated log files kept by --rotate-log; older ones are deleted
+    #[arg(long = "rotate-keep", default_value_t = 3, requires = "rotate_log")]
+    rotate_keep: usize,
+
+    /// Append to an existing log file instead of truncating it; --log-dir always starts
+    /// new files
+    #[arg(long = "csv-append", conflicts_with = "log_dir")]
+    csv_append: bool,
+
+    /// Do not write the CSV header row
+    #[arg(long = "csv-no-header")]
+    csv_no_header: bool,
+
+    /// Comma-separated list of GPU entity ids to monitor (e.g. 0,1,2)
+    #[arg(short = 'g', long = "gpus", visible_alias = "entity-id", value_delimiter = ',', default_value = "0")]
+    gpus: Vec<usize>,
+
+    /// Comma-separated `id:name` DCGM fields to monitor (e.g. 1002:SMACT,155:POWER)
+    #[arg(long = "fields", value_delimiter = ',', value_parser = parse_field)]
+    fields: Vec<(u32, String)>,
+
+    /// Also monitor the SM (SMCLK, field 100) and memory (MEMCLK, field 101) clocks in MHz,
+    /// charted against the rated maximum clocks reported by nvidia-smi
+    #[arg(long = "gpu-clock")]
+    gpu_clock: bool,
+
+    /// Also log derived metrics such as EFFIC, which are otherwise only shown
+    #[arg(long = "log-derived")]
+    log_derived: bool,
+
+    /// Chart NVLTX and NVLRX per lane by dividing them by this many NVLink lanes (12 on
+    /// an A100 SXM4); alerts, exports and the log keep the raw totals
+    #[arg(long = "nvlink-lanes", visible_alias = "nvlink-lane-count")]
+    nvlink_lanes: Option<u32>,
+
+    /// Replace NVLTX and NVLRX with one NVLBD row holding their sum
+    #[arg(long = "combine-nvlink")]
+    combine_nvlink: bool,
+
+    /// Replace PCITX and PCIRX with one PCIBD row holding their sum
+    #[arg(long = "combine-pcie")]
+    combine_pcie: bool,
+
+    /// Draw NVLRX stacked on top of NVLTX, and PCIRX on PCITX, in one row per link
+    #[arg(long = "stacked-bar", conflicts_with_all = ["combine_nvlink", "combine_pcie"])]
+    stacked_bar: bool,
+
+    /// Comma-separated metric names to keep from the monitored fields (e.g. SMACT,TENSO,FB_USED)
+    #[arg(long = "filter", value_delimiter = ',')]
+    filter: Vec<String>,
+
+    /// Display and log names `NAME:alias` for metrics, e.g. SMACT:SM_Active,FB_USED:VRAM_Used;
+    /// other options keep using the original names
+    #[arg(long = "field-alias", value_delimiter = ',', value_parser = parse_field_alias)]
+    field_aliases: Vec<(String, String)>,
+
+    /// Comma-separated percentiles shown in the stats panel (e.g. 50,95,99.9)
+    #[arg(long = "percentiles", value_delimiter = ',', value_parser = parse_percentile, default_value = "50,90,99")]
+    percentiles: Vec<f64>,
+
+    /// Alert rule such as `FB_USED>90` or `SMACT<0.1` (repeatable)
+    #[arg(long = "alert")]
+    alerts: Vec<String>,
+
+    /// Ring the terminal bell when a percentile stays above a level, e.g. `SMACT:p99:0.95` (repeatable)
+    #[arg(long = "percentile-alarm")]
+    percentile_alarms: Vec<String>,
+
+    /// Resample dcgmi's output to exactly this many samples per second per GPU
+    #[arg(long = "resample-hz", conflicts_with_all = ["replay", "interval_auto"])]
+    resample_hz: Option<f64>,
+
+    /// How --resample-hz computes each grid point from the samples around it
+    #[arg(long = "resample-interpolation", value_enum, default_value_t = Resampling::Linear, requires = "resample_hz")]
+    resample_interpolation: Resampling,
+
+    /// Drop samples arriving faster than this many per second per GPU
+    #[arg(long = "rate-limit")]
+    rate_limit: Option<f64>,
+
+    /// Exit with an error after this many consecutive unparseable sample lines
+    #[arg(long = "error-threshold")]
+    error_threshold: Option<u64>,
+
+    /// Hatch the charts and mark the stats `[stale: Ns]` once no sample has parsed for 3 intervals
+    #[arg(long = "warn-on-missing-samples", conflicts_with = "replay")]
+    warn_on_missing_samples: bool,
+
+    /// Exit with status 2 once SMACT stays under --gpu-util-threshold on every GPU for
+    /// --idle-duration seconds, after a warning and 5 s grace; any key restarts the countdown
+    #[arg(long = "idle-shutdown", conflicts_with = "replay")]
+    idle_shutdown: bool,
+
+    /// SMACT ratio under which a GPU counts as idle for --idle-shutdown
+    #[arg(long = "gpu-util-threshold", default_value_t = 0.05, requires = "idle_shutdown")]
+    gpu_util_threshold: f64,
+
+    /// Seconds every GPU must stay idle before --idle-shutdown warns
+    #[arg(long = "idle-duration", default_value_t = 30, requires = "idle_shutdown")]
+    idle_duration: u64,
+
+    /// Append dcgmi's stderr to this file instead of showing it in the footer
+    #[arg(long = "stderr-log")]
+    stderr_log: Option<String>,
+
+    /// Path to a file that alert firings are appended to (optional)
+    #[arg(long = "alert-log")]
+    alert_log: Option<String>,
+
+    /// Shell command run (via `sh -c`) when an --alert rule fires, at most once a minute per rule
+    #[arg(long = "trigger-cmd", requires = "alerts")]
+    trigger_cmd: Option<String>,
+
+    /// URL that each --alert firing is POSTed to as JSON (default: $DCGMI_TUI_WEBHOOK)
+    #[arg(long = "alert-webhook", requires = "alerts")]
+    alert_webhook: Option<String>,
+
+    /// Generate synthetic data instead of running dcgmi (no GPU needed)
+    #[arg(long = "mock")]
+    mock: bool,
+
+    /// Start with empty charts instead of restoring the previous session's history
+    #[arg(long = "no-restore")]
+    no_restore: bool,
+
+    /// Run headless: no terminal UI, only logging (and alerts to stderr or --alert-log);
+    /// needs --log or --headless-summary
+    #[arg(long = "no-tui")]
+    no_tui: bool,
+
+    /// Play back a CSV written by --log instead of running dcgmi
+    #[arg(long = "replay")]
+    replay: Option<String>,
+
+    /// Read raw `dcgmi dmon` output captured to a text file instead of running dcgmi
+    #[arg(long = "input-file", conflicts_with = "replay")]
+    input_file: Option<String>,
+
+    /// Playback speed multiplier for --replay (default 1.0). With --input-file, paces
+    /// samples at interval/speed instead of reading as fast as possible
+    #[arg(long = "replay-speed")]
+    replay_speed: Option<f64>,
+
+    /// JSON file of default arguments keyed by long option name; the command line overrides it
+    #[arg(long = "config")]
+    config: Option<String>,
+}
+
+impl Args {
+    /// Parses the command line, filling in anything it leaves out from the --config file.
+    /// Config values are turned back into arguments so they are validated exactly like
+    /// typed ones; unknown keys only produce a warning.
+    fn from_config_and_cli() -> Result<Args, AppError> {
+        let cli: Vec<std::ffi::OsString> = std::env::args_os().collect();
+        let command = Args::command();
+        // Only --config is wanted here; the final parse validates, once the config has
+        // had the chance to supply arguments that CLI flags `require`
+        let matches = command.clone().ignore_errors(true).get_matches_from(&cli);
+        let Some(path) = matches.get_one::<String>("config") else {
+            return Ok(Args::parse_from(cli));
+        };
+        let text = std::fs::read_to_string(path).map_err(AppError::from)?;
+        let config: serde_json::Value = serde_json::from_str(&text).map_err(|e| AppError::ParseError(format!("{}: {}", path, e)))?;
+        let serde_json::Value::Object(entries) = config else {
+            return Err(AppError::ParseError(format!("{}: expected a JSON object", path)));
+        };
+
+        let mut argv = vec![cli[0].clone()];
+        for (key, value) in entries {
+            let Some(arg) = command.get_arguments().find(|a| a.get_long() == Some(key.as_str()) && a.get_id() != "config") else {
+                eprintln!("warning: ignoring unknown key `{}` in {}", key, path);
+                continue;
+            };
+            if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+                continue;
+            }
+            let flag = format!("--{}", key);
+            let scalar = |v: &serde_json::Value| match v {
+                serde_json::Value::String(s) => Some(s.clone()),
+                serde_json::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            };
+            match value {
+                serde_json::Value::Bool(true) => argv.push(flag.into()),
+                serde_json::Value::Bool(false) | serde_json::Value::Null => {}
+                serde_json::Value::Array(items) => {
+                    for item in &items {
+                        match scalar(item) {
+                            Some(item) => argv.extend([flag.clone().into(), item.into()]),
+                            None => eprintln!("warning: ignoring non-scalar item in `{}` in {}", key, path),
+                        }
+                    }
+                }
+                other => match scalar(&other) {
+                    Some(v) => argv.extend([flag.into(), v.into()]),
+                    None => eprintln!("warning: ignoring object value for `{}` in {}", key, path),
+                },
+            }
+        }
+        argv.extend(cli.into_iter().skip(1));
+        Ok(Args::parse_from(argv))
+    }
+}
+
+/// How `--aggregate` combines the same metric across GPUs
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Aggregate {
+    Sum,
+    Mean,
+    Max,
+    Min,
+}
+
+impl Aggregate {
+    fn apply(self, values: &[f64]) -> f64 {
+        match self {
+            Aggregate::Sum => values.iter().sum(),
+            Aggregate::Mean => values.iter().sum::<f64>() / values.len().max(1) as f64,
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "SUM",
+            Aggregate::Mean => "MEAN",
+            Aggregate::Max => "MAX",
+            Aggregate::Min => "MIN",
+        }
+    }
+}
+
+/// Per-entity values that take part in aggregation: MIG instances are skipped when
+/// their parent GPU is reported too, so sums do not count the same work twice
+fn aggregated_values<T>(by_entity: &BTreeMap<Entity, T>) -> impl Iterator<Item = &T> {
+    by_entity.iter()
+        .filter(|(entity, _)| entity.instance.is_none() || !by_entity.contains_key(&Entity::gpu(entity.gpu)))
+        .map(|(_, value)| value)
+}
+
+/// Combines every GPU's buffers into one set. Buffers are aligned on their newest
+/// sample, and positions only some GPUs have yet are aggregated over those GPUs.
+fn aggregate_history(history: &BTreeMap<Entity, Vec<MetricBuffer>>, aggregate: Aggregate, metric_count: usize) -> Vec<MetricBuffer> {
+    (0..metric_count).map(|metric| {
+        let buffers: Vec<&MetricBuffer> = aggregated_values(history).map(|buffers| &buffers[metric]).collect();
+        let len = buffers.iter().map(|buf| buf.len()).max().unwrap_or(0);
+        (0..len).rev().map(|age| {
+            let values: Vec<f64> = buffers.iter()
+                .filter_map(|buf| buf.len().checked_sub(age + 1).map(|i| buf[i]))
+                .collect();
+            aggregate.apply(&values)
+        }).collect()
+    }).collect()
+}
+
+/// Per-metric `a - b`, aligned on the newest sample of each and as long as the shorter
+fn diff_history(history: &BTreeMap<Entity, Vec<MetricBuffer>>, a: Entity, b: Entity, metric_count: usize) -> Vec<MetricBuffer> {
+    let (Some(a), Some(b)) = (history.get(&a), history.get(&b)) else {
+        return vec![MetricBuffer::new(); metric_count];
+    };
+    a.iter().zip(b).map(|(a, b)| {
+        let len = a.len().min(b.len());
+        a.range(a.len() - len..).zip(b.range(b.len() - len..)).map(|(x, y)| x - y).collect()
+    }).collect()
+}
+
+/// Mean absolute value and the value furthest from zero (keeping its sign)
+fn diff_stats(buf: &MetricBuffer) -> (f64, f64) {
+    let mean_abs = buf.iter().map(|v| v.abs()).sum::<f64>() / buf.len().max(1) as f64;
+    let max = buf.iter().copied().fold(0.0, |max: f64, v| if v.abs() > max.abs() { v } else { max });
+    (mean_abs, max)
+}
+
+/// `format_metric_value` with an explicit sign, for --diff-gpu values
+fn format_signed(kind: MetricKind, value: f64) -> String {
+    let sign = if value < 0.0 { "-" } else { "+" };
+    format!("{}{}", sign, format_metric_value(kind, value.abs()))
+}
+
+/// Fires when the latest value of `metric` compares to `threshold` as `op`
+#[derive(Clone, Debug)]
+struct AlertRule {
+    metric: usize,
+    op: Ordering,
+    threshold: f64,
+}
+
+fn parse_field(s: &str) -> Result<(u32, String), String> {
+    let (id, name) = s.split_once(':').ok_or_else(|| format!("expected id:name, got `{}`", s))?;
+    let id = id.trim().parse().map_err(|_| format!("invalid field id in `{}`", s))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("missing name in `{}`", s));
+    }
+    Ok((id, name.to_string()))
+}
+
+/// Traffic-light levels for one metric, in the metric's raw units
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Thresholds {
+    warn: f64,
+    critical: f64,
+}
+
+/// Parses one `NAME:warn:critical` entry of --metric-thresholds
+fn parse_metric_threshold(s: &str) -> Result<(String, Thresholds), String> {
+    let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+    let [name, warn, critical] = parts[..] else {
+        return Err(format!("expected NAME:warn:critical, got `{}`", s));
+    };
+    let number = |v: &str| v.parse::<f64>().map_err(|_| format!("invalid threshold `{}` in `{}`", v, s));
+    let (warn, critical) = (number(warn)?, number(critical)?);
+    if warn > critical {
+        return Err(format!("warn threshold must not exceed critical in `{}`", s));
+    }
+    Ok((name.to_string(), Thresholds { warn, critical }))
+}
+
+/// One --temp-ranges band, covering `low` up to but not including `high`
+#[derive(Clone, Debug, PartialEq)]
+struct TempRange {
+    name: String,
+    color: Color,
+    low: f64,
+    high: f64,
+}
+
+/// A ratatui colour name (`cyan`, `lightred`), hex (`#ff8800`) or 256-colour index
+fn parse_colour(s: &str) -> Result<Color, String> {
+    s.trim().parse().map_err(|_| format!("unknown colour `{}`", s))
+}
+
+/// Parses one `name:colour:low-high` entry of --temp-ranges; colours are ratatui names
+/// (`blue`, `lightred`), hex (`#ff8800`) or 256-colour indices
+fn parse_temp_range(s: &str) -> Result<TempRange, String> {
+    let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+    let [name, color, range] = parts[..] else {
+        return Err(format!("expected name:colour:low-high, got `{}`", s));
+    };
+    let color = color.parse().map_err(|_| format!("unknown colour `{}` in `{}`", color, s))?;
+    // The separator is the first `-` after the low bound's optional sign, so `-10-40` parses
+    let bounds = range.char_indices().skip(1).find(|&(_, c)| c == '-')
+        .and_then(|(i, _)| Some((range[..i].trim().parse::<f64>().ok()?, range[i + 1..].trim().parse::<f64>().ok()?)));
+    let Some((low, high)) = bounds.filter(|(low, high)| low < high) else {
+        return Err(format!("expected an increasing low-high range in `{}`", s));
+    };
+    Ok(TempRange { name: name.to_string(), color, low, high })
+}
+
+fn parse_field_alias(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((name, alias)) if !alias.trim().is_empty() && !alias.contains(',') => Ok((name.trim().to_string(), alias.trim().to_string())),
+        _ => Err(format!("expected NAME:alias, got `{}`", s)),
+    }
+}
+
+fn parse_max_value(s: &str) -> Result<(String, f64), String> {
+    let Some((name, max)) = s.split_once(':') else {
+        return Err(format!("expected NAME:max, got `{}`", s));
+    };
+    match max.trim().parse::<f64>() {
+        Ok(max) if max > 0.0 => Ok((name.trim().to_string(), max)),
+        _ => Err(format!("invalid maximum `{}` in `{}`", max, s)),
+    }
+}
+
+fn parse_metric_scale(s: &str) -> Result<(String, f64), String> {
+    let Some((name, factor)) = s.split_once(':') else {
+        return Err(format!("expected NAME:factor, got `{}`", s));
+    };
+    match factor.trim().parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor != 0.0 => Ok((name.trim().to_string(), factor)),
+        _ => Err(format!("invalid factor `{}` in `{}`", factor, s)),
+    }
+}
+
+fn parse_metric_unit(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((name, unit)) if !unit.trim().is_empty() => Ok((name.trim().to_string(), unit.trim().to_string())),
+        _ => Err(format!("expected NAME:unit, got `{}`", s)),
+    }
+}
+
+/// Looks up a metric by display name, with an error listing the valid names
+fn metric_index(names: &[String], name: &str) -> Result<usize, String> {
+    names.iter().position(|m| m == name)
+        .ok_or_else(|| format!("unknown metric `{}` (expected one of {})", name, names.join(", ")))
+}
+
+/// TX/RX field pairs that --combine-nvlink and --combine-pcie fold into one bidirectional metric
+const NVLINK_PAIR: (u32, u32, &str) = (1011, 1012, "NVLBD");
+const PCIE_PAIR: (u32, u32, &str) = (1009, 1010, "PCIBD");
+
+/// Field id and display name of one metric
+type Field = (u32, String);
+
+/// Field id given to EFFIC; DCGM never assigns 0 to a real field
+const EFFIC_FIELD: u32 = 0;
+
+/// Inputs of the EFFIC metric, SMACT divided by the fraction of memory in use
+struct Efficiency {
+    smact: usize,
+    fb_used: usize,
+    /// Total memory in MB per GPU index, read once at startup
+    total_vram: BTreeMap<usize, f64>,
+}
+
+impl Efficiency {
+    /// EFFIC for one sample; 0 while no memory is in use or the GPU's total is unknown
+    fn value(&self, gpu_id: Entity, raw: &[f64]) -> f64 {
+        let used = self.total_vram.get(&gpu_id.gpu).map_or(0.0, |total| raw[self.fb_used] / total);
+        if used > 0.0 { raw[self.smact] / used } else { 0.0 }
+    }
+}
+
+/// Folds each `(tx, rx, name)` pair into a single metric at the TX field's position, returning
+/// the shown fields and, for each of them, the dcgmi columns it sums. The combined metric keeps
+/// the TX field id so it formats and groups like the fields it replaces.
+fn combine_fields(fields: &[Field], pairs: &[(u32, u32, &str)]) -> Result<(Vec<Field>, Vec<Vec<usize>>), String> {
+    let mut shown = Vec::new();
+    let mut columns = Vec::new();
+    for (i, (id, name)) in fields.iter().enumerate() {
+        match pairs.iter().find(|(tx, rx, _)| id == tx || id == rx) {
+            Some(&(tx, rx, combined)) if *id == tx => {
+                let missing = || format!("{} sums fields {} and {}, so both must be monitored", combined, tx, rx);
+                let rx_column = fields.iter().position(|(f, _)| *f == rx).ok_or_else(missing)?;
+                shown.push((tx, combined.to_string()));
+                columns.push(vec![i, rx_column]);
+            }
+            Some(&(tx, rx, combined)) => {
+                if !fields.iter().any(|(f, _)| *f == tx) {
+                    return Err(format!("{} sums fields {} and {}, so both must be monitored", combined, tx, rx));
+                }
+            }
+            None => {
+                shown.push((*id, name.clone()));
+                columns.push(vec![i]);
+            }
+        }
+    }
+    Ok((shown, columns))
+}
+
+fn parse_alert_rule(s: &str, names: &[String]) -> Result<AlertRule, String> {
+    let pos = s.find(['<', '>', '=']).ok_or_else(|| format!("expected <, > or = in alert `{}`", s))?;
+    let (name, rest) = s.split_at(pos);
+    let metric = metric_index(names, name.trim())?;
+    let op = match &rest[..1] {
+        "<" => Ordering::Less,
+        ">" => Ordering::Greater,
+        _ => Ordering::Equal,
+    };
+    let threshold = rest[1..].trim().parse().map_err(|_| format!("invalid threshold in alert `{}`", s))?;
+    Ok(AlertRule { metric, op, threshold })
+}
+
+/// Consecutive ticks a percentile must stay above its level before the bell rings
+const ALARM_TICKS: u32 = 3;
+/// An alarm re-arms once its percentile drops below this fraction of the level
+const ALARM_REARM: f64 = 0.9;
+
+/// Shortest gap between two --trigger-cmd runs for the same alert rule
+const TRIGGER_COOLDOWN: Duration = Duration::from_secs(60);
+/// Wait before the one retry of a failed --alert-webhook POST
+const WEBHOOK_RETRY: Duration = Duration::from_secs(2);
+/// Longest a single --alert-webhook POST may take
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs --trigger-cmd on its own thread so a slow command never stalls drawing. The alert is
+/// passed in `DCGMI_ALERT`, `DCGMI_GPU` and `DCGMI_VALUE`; output is discarded while the TUI
+/// owns the terminal.
+fn run_trigger(cmd: &str, rule: String, gpu_id: Entity, value: f64, quiet: bool) {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd)
+        .env("DCGMI_ALERT", rule)
+        .env("DCGMI_GPU", gpu_id.to_string())
+        .env("DCGMI_VALUE", value.to_string())
+        .stdin(Stdio::null());
+    if quiet {
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+    }
+    thread::spawn(move || {
+        if let Err(e) = command.status() {
+            if !quiet {
+                eprintln!("--trigger-cmd failed to start: {}", e);
+            }
+        }
+    });
+}
+
+/// POSTs `payload` to `url` on a background thread, retrying once after `WEBHOOK_RETRY`;
+/// a second failure is dropped so an unreachable endpoint never disturbs sampling
+fn post_webhook(url: &str, payload: serde_json::Value) {
+    let url = url.to_string();
+    thread::spawn(move || {
+        let body = payload.to_string();
+        let post = || ureq::post(&url).timeout(WEBHOOK_TIMEOUT).set("Content-Type", "application/json").send_string(&body).is_ok();
+        if !post() {
+            thread::sleep(WEBHOOK_RETRY);
+            post();
+        }
+    });
+}
+
+/// --percentile-alarm rule with its per-GPU trigger state
+#[derive(Clone, Debug)]
+struct PercentileAlarm {
+    metric: usize,
+    pct: f64,
+    threshold: f64,
+    /// Times this rule has rung the bell
+    bell_count: u32,
+    /// Per GPU: consecutive ticks above the level, and whether the bell may ring again
+    state: BTreeMap<Entity, (u32, bool)>,
+}
+
+/// Parses `NAME:pNN:threshold`
+fn parse_percentile_alarm(s: &str, names: &[String]) -> Result<PercentileAlarm, String> {
+    let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+    let [name, pct, threshold] = parts[..] else {
+        return Err(format!("expected NAME:pNN:threshold, got `{}`", s));
+    };
+    let metric = metric_index(names, name)?;
+    let pct = parse_percentile(pct.strip_prefix('p').unwrap_or(pct))?;
+    let threshold = threshold.parse().map_err(|_| format!("invalid threshold `{}` in `{}`", threshold, s))?;
+    Ok(PercentileAlarm { metric, pct, threshold, bell_count: 0, state: BTreeMap::new() })
+}
+
+impl PercentileAlarm {
+    fn describe(&self, names: &[String]) -> String {
+        format!("{} p{}>{}", names[self.metric], self.pct, self.threshold)
+    }
+}
+
+impl AlertRule {
+    fn matches(&self, value: f64) -> bool {
+        value.partial_cmp(&self.threshold) == Some(self.op)
+    }
+
+    fn describe(&self, names: &[String]) -> String {
+        let op = match self.op {
+            Ordering::Less => "<",
+            Ordering::Greater => ">",
+            Ordering::Equal => "=",
+        };
+        format!("{}{}{}", names[self.metric], op, self.threshold)
+    }
+}
+
+const CUSTOM_SET: Set = Set {
+    empty: " ",
+    one_eighth: "▁",
+    one_quarter: "▂",
+    three_eighths: "▃",
+    half: "▄",
+    five_eighths: "▅",
+    three_quarters: "▆",
+    seven_eighths: "▇",
+    full: "█",
+};
+
+/// Latest value, p50 and p90 of every metric, per GPU, shared with the HTTP and socket threads
+#[derive(Default)]
+struct LatestStats {
+    names: Vec<String>,
+    gpus: BTreeMap<Entity, Vec<[f64; 3]>>,
+}
+
+/// Renders the shared state in the Prometheus text exposition format
+fn render_prometheus(state: &LatestStats) -> String {
+    let mut out = String::new();
+    for (i, name) in state.names.iter().enumerate() {
+        let base: String = name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect();
+        for (suffix, stat) in [("", 0), ("_p50", 1), ("_p90", 2)] {
+            let metric = format!("dcgmi_{}{}", base, suffix);
+            out.push_str(&format!("# TYPE {} gauge\n", metric));
+            for (entity, values) in &state.gpus {
+                let labels = match entity.instance {
+                    Some(instance) => format!("gpu=\"{}\",mig=\"{}\"", entity.gpu, instance),
+                    None => format!("gpu=\"{}\"", entity.gpu),
+                };
+                out.push_str(&format!("{}{{{}}} {}\n", metric, labels, values[i][stat]));
+            }
+        }
+    }
+    out
+}
+
+/// Serves `GET /metrics` from a background thread so scrapes never block the UI
+fn spawn_prometheus_server(bind: IpAddr, port: u16, state: Arc<Mutex<LatestStats>>) -> std::io::Result<()> {
+    let server = tiny_http::Server::http((bind, port)).map_err(std::io::Error::other)?;
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if *request.method() == tiny_http::Method::Get && request.url().split('?').next() == Some("/metrics") {
+                let body = render_prometheus(&state.lock().unwrap());
+                let content_type = tiny_http::Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap();
+                tiny_http::Response::from_string(body).with_header(content_type)
+            } else {
+                tiny_http::Response::from_string("not found\n").with_status_code(404)
+            };
+            request.respond(response).ok();
+        }
+    });
+    Ok(())
+}
+
+/// Latest value of every metric as `{"<gpu>": {"<name>": value, ...}, ...}`
+fn render_latest_json(state: &LatestStats) -> String {
+    let gpus: serde_json::Map<String, serde_json::Value> = state.gpus.iter().map(|(gpu, values)| {
+        let metrics: serde_json::Map<String, serde_json::Value> = state.names.iter().zip(values)
+            .map(|(name, stats)| (name.clone(), stats[0].into()))
+            .collect();
+        (gpu.to_string(), metrics.into())
+    }).collect();
+    serde_json::Value::Object(gpus).to_string()
+}
+
+/// Listens on a Unix socket; every client is sent the latest values as JSON and disconnected
+fn spawn_socket_server(path: &str, state: Arc<Mutex<LatestStats>>) -> std::io::Result<()> {
+    // A leftover file from a crashed run blocks bind; one that still accepts connections is in use
+    if std::path::Path::new(path).exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(std::io::ErrorKind::AddrInUse, format!("{} is in use by another instance", path)));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    thread::spawn(move || {
+        for mut stream in listener.incoming().map_while(Result::ok) {
+            let body = render_latest_json(&state.lock().unwrap());
+            writeln!(stream, "{}", body).ok();
+        }
+    });
+    Ok(())
+}
+
+/// Output of the dcgmi child, forwarded from its reader threads
+enum SourceEvent {
+    /// Every `GPU ...` line of one sampling tick, in the order dcgmi printed them
+    Tick(Vec<String>),
+    /// Any other stdout line, such as the column headers dmon repeats
+    Stdout(String),
+    Stderr(String),
+    /// stdout reached end-of-file, usually because the process exited
+    Closed,
+}
+
+/// Groups dmon's `GPU ...` lines into ticks. dmon prints no end-of-tick marker, so a
+/// tick ends at the next non-sample line (its periodic header) or when an entity that is
+/// already in the current tick shows up again.
+#[derive(Default)]
+struct TickAssembler {
+    lines: Vec<String>,
+    /// Entity ids (the token after `GPU`) seen in the current tick
+    seen: HashSet<String>,
+}
+
+impl TickAssembler {
+    /// Feeds one stdout line, returning the events it completes
+    fn push(&mut self, line: String) -> Vec<SourceEvent> {
+        let mut tokens = line.split_whitespace();
+        let entity = match (tokens.next(), tokens.next()) {
+            (Some("GPU"), Some(id)) => Some(id.to_string()),
+            _ => None,
+        };
+        let mut events = Vec::new();
+        match entity {
+            Some(id) => {
+                if self.seen.contains(&id) {
+                    events.extend(self.flush());
+                }
+                self.seen.insert(id);
+                self.lines.push(line);
+            }
+            None => {
+                events.extend(self.flush());
+                events.push(SourceEvent::Stdout(line));
+            }
+        }
+        events
+    }
+
+    /// Hands over the lines collected so far as a tick, if there are any
+    fn flush(&mut self) -> Option<SourceEvent> {
+        self.seen.clear();
+        (!self.lines.is_empty()).then(|| SourceEvent::Tick(std::mem::take(&mut self.lines)))
+    }
+}
+
+/// How --resample-hz fills in a grid point from the samples either side of it
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Resampling {
+    /// Weighted by how close each sample is to the grid point
+    Linear,
+    /// The value of whichever sample is closer
+    Nearest,
+}
+
+/// How long a grid point waits for every entity's next sample before it is emitted without
+/// the entities that have fallen silent
+const RESAMPLE_GRACE: Duration = Duration::from_secs(1);
+
+/// Turns `GPU ...` lines arriving at irregular times into ticks on a fixed grid. Each grid
+/// point is emitted once every entity has a sample at or after it, so a tick still holds all
+/// of them.
+struct Resampler {
+    period: f64,
+    mode: Resampling,
+    /// Per entity id: the last sample (seconds since start, values) and the next grid index it owes
+    entities: BTreeMap<String, (f64, Vec<f64>, u64)>,
+    /// Lines computed per grid index, waiting for the slower entities
+    pending: BTreeMap<u64, Vec<String>>,
+}
+
+impl Resampler {
+    fn new(hz: f64, mode: Resampling) -> Resampler {
+        Resampler { period: 1.0 / hz, mode, entities: BTreeMap::new(), pending: BTreeMap::new() }
+    }
+
+    /// Feeds one line received `at` seconds after start; lines that are not numeric samples
+    /// are handed back so they still reach the parser (and its error count)
+    fn push(&mut self, line: String, at: f64) -> Option<String> {
+        let mut tokens = line.split_whitespace();
+        let (Some("GPU"), Some(id)) = (tokens.next(), tokens.next()) else {
+            return Some(line);
+        };
+        let Ok(values) = tokens.map(str::parse).collect::<Result<Vec<f64>, _>>() else {
+            return Some(line);
+        };
+        let Some((last_at, last, next)) = self.entities.get_mut(id) else {
+            let first = (at / self.period).ceil() as u64;
+            self.entities.insert(id.to_string(), (at, values, first));
+            return None;
+        };
+        while *next as f64 * self.period <= at {
+            let t = *next as f64 * self.period;
+            let point: Vec<f64> = match self.mode {
+                Resampling::Linear => {
+                    let weight = if at > *last_at { (t - *last_at) / (at - *last_at) } else { 1.0 };
+                    last.iter().zip(&values).map(|(a, b)| a + (b - a) * weight).collect()
+                }
+                Resampling::Nearest if t - *last_at < at - t => last.clone(),
+                Resampling::Nearest => values.clone(),
+            };
+            let point: Vec<String> = point.iter().map(f64::to_string).collect();
+            self.pending.entry(*next).or_default().push(format!("GPU {} {}", id, point.join(" ")));
+            *next += 1;
+        }
+        *last_at = at;
+        *last = values;
+        None
+    }
+
+    /// Ticks for the grid points every entity has reached, and for those older than the grace
+    /// period; a `now` of infinity flushes everything
+    fn ready(&mut self, now: f64) -> Vec<SourceEvent> {
+        let reached = self.entities.values().map(|(_, _, next)| *next).min().unwrap_or(0);
+        let mut ticks = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            let t = *entry.key() as f64 * self.period;
+            if *entry.key() >= reached && t >= now - RESAMPLE_GRACE.as_secs_f64() {
+                break;
+            }
+            ticks.push(SourceEvent::Tick(entry.remove()));
+        }
+        ticks
+    }
+}
+
+/// Puts a resampling thread between a source and the event loop
+fn spawn_resampler(source: Receiver<SourceEvent>, hz: f64, mode: Resampling) -> Receiver<SourceEvent> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let start = Instant::now();
+        let mut resampler = Resampler::new(hz, mode);
+        let period = Duration::from_secs_f64(1.0 / hz);
+        loop {
+            let mut events = Vec::new();
+            match source.recv_timeout(period) {
+                Ok(SourceEvent::Tick(lines)) => {
+                    let at = start.elapsed().as_secs_f64();
+                    let unparsed: Vec<String> = lines.into_iter().filter_map(|line| resampler.push(line, at)).collect();
+                    if !unparsed.is_empty() {
+                        events.push(SourceEvent::Tick(unparsed));
+                    }
+                }
+                // Whatever is still pending goes out before the source's end
+                Ok(SourceEvent::Closed) => {
+                    for event in resampler.ready(f64::INFINITY).into_iter().chain([SourceEvent::Closed]) {
+                        if tx.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    return;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                Ok(event) => events.push(event),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+            }
+            events.extend(resampler.ready(start.elapsed().as_secs_f64()));
+            for event in events {
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Starts `dcgmi dmon` with stdout and stderr each drained by their own thread
+/// `target` selects what dcgmi watches, e.g. `["--entity-id", "0,1"]` or `["--group-id", "0"]`
+fn spawn_dcgmi(field_ids: &str, target: &[String], interval_ms: u64) -> std::io::Result<(Child, Receiver<SourceEvent>)> {
+    let mut child = Command::new("dcgmi")
+        .arg("dmon")
+        .arg("-e")
+        .arg(field_ids)
+        .args(target)
+        .arg("-d").arg(interval_ms.to_string())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    let stdout_tx = tx.clone();
+    thread::spawn(move || {
+        let mut ticks = TickAssembler::default();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            for event in ticks.push(line) {
+                if stdout_tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+        if let Some(tick) = ticks.flush() {
+            stdout_tx.send(tick).ok();
+        }
+        stdout_tx.send(SourceEvent::Closed).ok();
+    });
+    let stderr = child.stderr.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if tx.send(SourceEvent::Stderr(line)).is_err() {
+                return;
+            }
+        }
+    });
+    Ok((child, rx))
+}
+
+/// A live sampler that can be (re)started at a given interval. Every source emits
+/// `dmon`-style `GPU <id> <values...>` lines, so the rest of the tool treats them alike.
+trait MetricSource {
+    /// Name used in status and error messages
+    fn name(&self) -> &'static str;
+    /// Starts sampling every `interval_ms`, stopping any run already going
+    fn start(&mut self, interval_ms: u64) -> std::io::Result<Receiver<SourceEvent>>;
+    /// Once the output has closed: how the source ended, or `None` while it is still running
+    fn exit_status(&mut self) -> std::io::Result<Option<String>>;
+    fn stop(&mut self);
+}
+
+/// `dcgmi dmon` as a child process
+struct DcgmiSource {
+    field_ids: String,
+    target: Vec<String>,
+    child: Option<Child>,
+}
+
+impl MetricSource for DcgmiSource {
+    fn name(&self) -> &'static str {
+        "dcgmi"
+    }
+
+    fn start(&mut self, interval_ms: u64) -> std::io::Result<Receiver<SourceEvent>> {
+        self.stop();
+        let (child, rx) = spawn_dcgmi(&self.field_ids, &self.target, interval_ms)?;
+        self.child = Some(child);
+        Ok(rx)
+    }
+
+    fn exit_status(&mut self) -> std::io::Result<Option<String>> {
+        match self.child {
+            Some(ref mut child) => Ok(child.try_wait()?.map(|status| status.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
+
+/// NVML read directly on a background thread, for machines without DCGM (--nvml-fallback)
+struct NvmlSource {
+    reader: Arc<NvmlReader>,
+    field_ids: Vec<u32>,
+    gpus: Vec<Entity>,
+    /// Tells the running sampler thread to finish
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl MetricSource for NvmlSource {
+    fn name(&self) -> &'static str {
+        "NVML"
+    }
+
+    fn start(&mut self, interval_ms: u64) -> std::io::Result<Receiver<SourceEvent>> {
+        self.stop();
+        self.stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let (reader, stop, field_ids) = (self.reader.clone(), self.stop.clone(), self.field_ids.clone());
+        // NVML has no notion of MIG instances in these queries, so only whole GPUs are read
+        let gpus: Vec<usize> = self.gpus.iter().filter(|e| e.instance.is_none()).map(|e| e.gpu).collect();
+        self.handle = Some(thread::spawn(move || {
+            while !stop.load(AtomicOrdering::SeqCst) {
+                let tick: Result<Vec<String>, String> = gpus.iter().map(|&gpu| {
+                    let values = reader.sample(gpu as u32, &field_ids)?;
+                    Ok(format!("GPU {} {}", gpu, values.iter().map(f64::to_string).collect::<Vec<_>>().join(" ")))
+                }).collect();
+                let event = match tick {
+                    Ok(lines) => SourceEvent::Tick(lines),
+                    Err(e) => {
+                        tx.send(SourceEvent::Stderr(e)).ok();
+                        tx.send(SourceEvent::Closed).ok();
+                        return;
+                    }
+                };
+                if tx.send(event).is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(interval_ms));
+            }
+        }));
+        Ok(rx)
+    }
+
+    fn exit_status(&mut self) -> std::io::Result<Option<String>> {
+        Ok(self.handle.as_ref().filter(|handle| handle.is_finished()).map(|_| "a read failed".to_string()))
+    }
+
+    fn stop(&mut self) {
+        self.stop.store(true, AtomicOrdering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+/// Whether `program` is an executable file in one of the `PATH` directories
+fn in_path(program: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| std::fs::metadata(dir.join(program)).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0))
+    })
+}
+
+/// Emits fake `dcgmi dmon` lines every `interval_ms`, each metric following its own sine wave
+fn spawn_mock(fields: &[(u32, String)], gpus: &[Entity], interval_ms: u64) -> Receiver<SourceEvent> {
+    let kinds: Vec<MetricKind> = fields.iter().map(|(id, _)| metric_kind(*id)).collect();
+    let gpus = gpus.to_vec();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let start = Instant::now();
+        loop {
+            let t = start.elapsed().as_secs_f64();
+            let mut tick = Vec::with_capacity(gpus.len());
+            for &gpu in &gpus {
+                let values: Vec<String> = kinds.iter().enumerate().map(|(i, kind)| {
+                    let freq = 0.05 + 0.04 * i as f64;
+                    let amplitude = 0.3 + 0.07 * ((i * 7) % 10) as f64;
+                    let phase = i as f64 + gpu.gpu as f64 * 0.7 + gpu.instance.map_or(0.0, |n| n as f64 * 1.9);
+                    let unit = (0.5 + amplitude * (std::f64::consts::TAU * freq * t + phase).sin()).clamp(0.0, 1.0);
+                    let value = match kind {
+                        MetricKind::Ratio => unit,
+                        MetricKind::BytesPerSec => unit * 8e9,
+                        MetricKind::Megabytes => unit * 40960.0,
+                        MetricKind::Celsius => 30.0 + unit * 60.0,
+                        MetricKind::Watts => 50.0 + unit * 350.0,
+                        MetricKind::Megahertz => 300.0 + unit * 1200.0,
+                        MetricKind::Plain => unit * 100.0,
+                    };
+                    format!("{:.3}", value)
+                }).collect();
+                tick.push(format!("GPU {} {}", gpu, values.join(" ")));
+            }
+            if tx.send(SourceEvent::Tick(tick)).is_err() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(interval_ms));
+        }
+    });
+    rx
+}
+
+/// Whether `pid` is still running; zombies count as exited since they will never run again
+fn process_alive(pid: u32) -> bool {
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+        return false;
+    };
+    // The state follows the parenthesised command name, which may itself contain spaces or ')'
+    let state = stat.rfind(')').and_then(|i| stat[i + 1..].split_whitespace().next());
+    !matches!(state, Some("Z") | Some("X"))
+}
+
+/// Kernel clock ticks per second for /proc times; 100 on every mainstream Linux build
+const CLOCK_TICKS: f64 = 100.0;
+
+/// User plus system CPU time this process has used, from `/proc/self/stat`
+fn process_cpu_seconds() -> Option<f64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // utime and stime are fields 14 and 15; the state (field 3) is the first after the name
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let ticks = fields.get(11)?.parse::<f64>().ok()? + fields.get(12)?.parse::<f64>().ok()?;
+    Some(ticks / CLOCK_TICKS)
+}
+
+/// How often --interval-auto measures CPU usage and reconsiders the interval
+const AUTO_INTERVAL_PERIOD: Duration = Duration::from_secs(5);
+/// Factor the interval grows or shrinks by per adjustment
+const AUTO_INTERVAL_STEP: f64 = 1.5;
+
+/// Time between the --idle-shutdown warning and the exit
+const IDLE_GRACE: Duration = Duration::from_secs(5);
+
+/// --idle-shutdown countdown over the latest SMACT of every GPU
+struct IdleShutdown {
+    smact: usize,
+    threshold: f64,
+    duration: Duration,
+    /// When every GPU last dropped under the threshold; `None` while any is busy
+    since: Option<Instant>,
+    warned: bool,
+}
+
+enum IdleEvent {
+    /// Idle for the full duration; the exit follows after `IDLE_GRACE`
+    Warned,
+    Expired,
+}
+
+impl IdleShutdown {
+    fn new(smact: usize, threshold: f64, duration: Duration) -> Self {
+        IdleShutdown { smact, threshold, duration, since: None, warned: false }
+    }
+
+    /// Restarts the countdown, as a keypress does
+    fn reset(&mut self) {
+        self.since = None;
+        self.warned = false;
+    }
+
+    fn update(&mut self, history: &BTreeMap<Entity, Vec<MetricBuffer>>) -> Option<IdleEvent> {
+        let latest: Vec<f64> = history.values().filter_map(|buffers| buffers[self.smact].back().copied()).collect();
+        // No samples yet is not idleness
+        if latest.is_empty() || latest.iter().any(|&smact| smact >= self.threshold) {
+            self.reset();
+            return None;
+        }
+        let idle_for = self.since.get_or_insert_with(Instant::now).elapsed();
+        if idle_for >= self.duration + IDLE_GRACE {
+            Some(IdleEvent::Expired)
+        } else if idle_for >= self.duration && !self.warned {
+            self.warned = true;
+            Some(IdleEvent::Warned)
+        } else {
+            None
+        }
+    }
+
+    /// Time left before the exit while the countdown runs
+    fn remaining(&self) -> Option<Duration> {
+        Some((self.duration + IDLE_GRACE).saturating_sub(self.since?.elapsed()))
+    }
+}
+
+/// --interval-auto controller: lengthens the interval while CPU usage is over the target
+/// and shortens it again once usage falls below half of it
+struct AutoInterval {
+    target_cpu: f64,
+    min_ms: u64,
+    max_ms: u64,
+    last_check: Instant,
+    last_cpu: f64,
+}
+
+impl AutoInterval {
+    fn new(target_cpu: f64, min_ms: u64, max_ms: u64) -> AutoInterval {
+        AutoInterval { target_cpu, min_ms: min_ms.max(1), max_ms: max_ms.max(min_ms).max(1), last_check: Instant::now(), last_cpu: process_cpu_seconds().unwrap_or_default() }
+    }
+
+    /// The interval to switch to, or `None` to keep `current` (also between measurements)
+    fn adjust(&mut self, current: u64) -> Option<u64> {
+        let elapsed = self.last_check.elapsed();
+        if elapsed < AUTO_INTERVAL_PERIOD {
+            return None;
+        }
+        let cpu = process_cpu_seconds()?;
+        let usage = (cpu - self.last_cpu) / elapsed.as_secs_f64() * 100.0;
+        self.last_check = Instant::now();
+        self.last_cpu = cpu;
+        let next = if usage > self.target_cpu {
+            (current as f64 * AUTO_INTERVAL_STEP) as u64
+        } else if usage < self.target_cpu / 2.0 {
+            (current as f64 / AUTO_INTERVAL_STEP) as u64
+        } else {
+            current
+        };
+        let next = next.clamp(self.min_ms, self.max_ms);
+        (next != current).then_some(next)
+    }
+}
+
+/// How often --show-procs lists each GPU's processes
+const PROCESS_POLL: Duration = Duration::from_secs(5);
+/// Height of the --show-procs panel, borders included
+const PROCESS_PANEL_ROWS: u16 = 7;
+/// Width of the A - B column between the two --dual-gpu-compare sides
+const DUAL_DELTA_WIDTH: u16 = 14;
+
+/// Sends every GPU's process list now and then every `PROCESS_POLL`; --mock invents two per GPU
+fn spawn_process_poller(gpus: Vec<usize>, mock: bool) -> Receiver<BTreeMap<usize, Vec<GpuProcess>>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || loop {
+        let processes = gpus
+            .iter()
+            .map(|&gpu| {
+                let listed = if mock {
+                    let pid = 4100 + 10 * gpu as u32;
+                    vec![
+                        GpuProcess { pid, name: "python train.py".to_string(), memory_mb: 18432.0 },
+                        GpuProcess { pid: pid + 1, name: "python eval.py".to_string(), memory_mb: 2048.0 },
+                    ]
+                } else {
+                    query_processes(gpu)
+                };
+                (gpu, listed)
+            })
+            .collect();
+        if tx.send(processes).is_err() {
+            return;
+        }
+        thread::sleep(PROCESS_POLL);
+    });
+    rx
+}
+
+/// Polls `pid` once a second and signals the returned channel when it exits
+fn spawn_pid_watcher(pid: u32) -> Receiver<()> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        while process_alive(pid) {
+            thread::sleep(Duration::from_secs(1));
+        }
+        tx.send(()).ok();
+    });
+    rx
+}
+
+/// Feeds a captured `dcgmi dmon` text file through the same channel as the live child.
+/// With `pace` set, sleeps that long after each tick; otherwise reads flat out.
+fn spawn_file_reader(path: &str, pace: Option<Duration>) -> std::io::Result<Receiver<SourceEvent>> {
+    let file = File::open(path)?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut ticks = TickAssembler::default();
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            for event in ticks.push(line) {
+                let is_tick = matches!(event, SourceEvent::Tick(_));
+                if tx.send(event).is_err() {
+                    return;
+                }
+                if let (true, Some(pace)) = (is_tick, pace) {
+                    thread::sleep(pace);
+                }
+            }
+        }
+        if let Some(tick) = ticks.flush() {
+            tx.send(tick).ok();
+        }
+        tx.send(SourceEvent::Closed).ok();
+    });
+    Ok(rx)
+}
+
+/// Reads a log timestamp written as rfc3339, unix_ms or unix_s (told apart by magnitude)
+fn parse_log_timestamp(s: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Some(ts);
+    }
+    let number: f64 = s.parse().ok()?;
+    // Unix seconds stay below 1e11 until the year 5138
+    let millis = if number < 1e11 { number * 1000.0 } else { number };
+    chrono::DateTime::from_timestamp_millis(millis as i64).map(|ts| ts.fixed_offset())
+}
+
+/// One row of a CSV log, positioned relative to the first row
+struct ReplayRow {
+    offset: Duration,
+    gpu_id: Entity,
+    values: Vec<f64>,
+}
+
+/// Plays back a CSV log at the pace implied by its timestamps
+struct Replay {
+    rows: Vec<ReplayRow>,
+    /// Number of rows that have been fed into the history so far
+    pos: usize,
+    /// Current position on the recording's own timeline
+    position: Duration,
+    speed: f64,
+    paused: bool,
+    last_update: Instant,
+}
+
+impl Replay {
+    /// Loads a log written by `spawn_logger_thread`. Columns are matched by header
+    /// name, so logs without a `gpu` column are treated as GPU 0 and metrics missing
+    /// from the file read as zero. --samplelog-bin ring files are recognised by their
+    /// magic bytes.
+    fn load(path: &str, speed: f64, names: &[String]) -> std::io::Result<Replay> {
+        if is_ring_log(path)? {
+            return Replay::load_ring(path, speed, names);
+        }
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines.next().transpose()?.ok_or_else(|| invalid(format!("{} is empty", path)))?;
+        // The header always starts with `timestamp`, so the next character is the delimiter
+        let delimiter = header.strip_prefix("timestamp").and_then(|rest| rest.chars().next()).unwrap_or(',');
+        // Aliased metric names may be quoted like the cells
+        let columns = split_csv_line(&header, delimiter);
+        let gpu_col = columns.iter().position(|c| c == "gpu");
+        let annotation_col = columns.iter().position(|c| c == "annotation");
+        let metric_cols: Vec<Option<usize>> = names.iter()
+            .map(|name| columns.iter().position(|c| c == name))
+            .collect();
+
+        let mut rows = Vec::new();
+        let mut first: Option<chrono::DateTime<chrono::FixedOffset>> = None;
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // Labels and annotations may be quoted and contain the delimiter
+            let fields = split_csv_line(&line, delimiter);
+            // Rows written by `a` only carry a label, not a sample
+            if annotation_col.and_then(|c| fields.get(c)).is_some_and(|text| !text.is_empty()) {
+                continue;
+            }
+            let ts = parse_log_timestamp(&fields[0])
+                .ok_or_else(|| invalid(format!("line {}: bad timestamp `{}`; replay needs rfc3339, unix_ms or unix_s timestamps", n + 2, fields[0])))?;
+            let start = *first.get_or_insert(ts);
+            let gpu_id = gpu_col.and_then(|c| fields.get(c)?.parse().ok()).unwrap_or(Entity::gpu(0));
+            let values = metric_cols.iter()
+                .map(|col| col.and_then(|c| fields.get(c)?.parse().ok()).unwrap_or(0.0))
+                .collect();
+            rows.push(ReplayRow {
+                offset: (ts - start).to_std().unwrap_or_default(),
+                gpu_id,
+                values,
+            });
+        }
+        Ok(Replay::new(rows, speed))
+    }
+
+    fn load_ring(path: &str, speed: f64, names: &[String]) -> std::io::Result<Replay> {
+        let log = read_ring_log(path)?;
+        let metric_cols: Vec<Option<usize>> = names.iter().map(|name| log.names.iter().position(|n| n == name)).collect();
+        let first = log.records.first().map_or(0, |(ts, _, _)| *ts);
+        let rows = log.records.into_iter().map(|(ts, gpu_id, values)| ReplayRow {
+            offset: Duration::from_millis(ts.saturating_sub(first).max(0) as u64),
+            gpu_id,
+            values: metric_cols.iter().map(|col| col.map_or(0.0, |c| values[c])).collect(),
+        }).collect();
+        Ok(Replay::new(rows, speed))
+    }
+
+    fn new(rows: Vec<ReplayRow>, speed: f64) -> Replay {
+        Replay {
+            rows,
+            pos: 0,
+            position: Duration::ZERO,
+            speed,
+            paused: false,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// GPU ids present in the recording, in order of first appearance
+    fn gpus(&self) -> Vec<Entity> {
+        let mut gpus = Vec::new();
+        for row in &self.rows {
+            if !gpus.contains(&row.gpu_id) {
+                gpus.push(row.gpu_id);
+            }
+        }
+        if gpus.is_empty() {
+            gpus.push(Entity::gpu(0));
+        }
+        gpus
+    }
+
+    fn total(&self) -> Duration {
+        self.rows.last().map(|r| r.offset).unwrap_or_default()
+    }
+
+    /// Moves the playback clock forward and returns the range of rows that became due.
+    fn advance(&mut self) -> std::ops::Range<usize> {
+        let now = Instant::now();
+        if !self.paused {
+            self.position += now.duration_since(self.last_update).mul_f64(self.speed);
+        }
+        self.last_update = now;
+        let start = self.pos;
+        while self.pos < self.rows.len() && self.rows[self.pos].offset <= self.position {
+            self.pos += 1;
+        }
+        start..self.pos
+    }
+
+    /// Pauses and moves one row forwards or backwards. Returns false at either end.
+    fn step(&mut self, forward: bool) -> bool {
+        self.paused = true;
+        if forward && self.pos < self.rows.len() {
+            self.pos += 1;
+        } else if !forward && self.pos > 1 {
+            self.pos -= 1;
+        } else {
+            return false;
+        }
+        self.position = self.rows[self.pos - 1].offset;
+        true
+    }
+}
+
+/// Per-metric p50 of a CSV written by --log, using the same column matching as replay
+fn load_baseline(path: &str, names: &[String]) -> std::io::Result<Vec<f64>> {
+    let rows = Replay::load(path, 1.0, names)?.rows;
+    Ok((0..names.len()).map(|metric| {
+        let buf: MetricBuffer = rows.iter().map(|row| row.values[metric]).collect();
+        buffer_percentiles(&buf, &[50.0])[0]
+    }).collect())
+}
+
+/// Short label for a chart's time span: `1.2s`, `10s`, `3m20s`
+fn format_window(d: Duration) -> String {
+    let secs = d.as_secs_f64();
+    if secs < 10.0 {
+        format!("{:.1}s", secs)
+    } else if secs < 60.0 {
+        format!("{:.0}s", secs)
+    } else {
+        format!("{}m{:02}s", d.as_secs() / 60, d.as_secs() % 60)
+    }
+}
+
+/// Formats as `mm:ss`, growing to `h:mm:ss` past the hour
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SummaryFormat {
+    /// Tab-separated with a header row
+    Tsv,
+    /// A JSON array with one object per GPU and metric
+    Json,
+}
+
+/// One --headless-summary row, in the metric's raw units
+#[derive(Serialize)]
+struct SummaryRow {
+    gpu: String,
+    metric: String,
+    min: f64,
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+/// On-disk schema of the `s` key snapshot
+#[derive(Serialize)]
+struct Snapshot {
+    timestamp: String,
+    gpus: Vec<GpuSnapshot>,
+}
+
+#[derive(Serialize)]
+struct GpuSnapshot {
+    gpu: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mig: Option<usize>,
+    metrics: Vec<MetricSnapshot>,
+}
+
+#[derive(Serialize)]
+struct MetricSnapshot {
+    name: String,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    values: Vec<f64>,
+}
+
+/// History saved on quit and restored on the next launch
+#[derive(Serialize, Deserialize)]
+struct SessionFile {
+    /// Fields the buffers were recorded with; a mismatch discards the file
+    fields: Vec<(u32, String)>,
+    history: BTreeMap<Entity, Vec<MetricBuffer>>,
+}
+
+/// `$XDG_DATA_HOME/dcgmi_tui/last_session.bin`, falling back to `~/.local/share`
+fn session_path() -> Option<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".local/share")))?;
+    Some(base.join("dcgmi_tui").join("last_session.bin"))
+}
+
+/// Writes the session to a temporary file and renames it over the old one so a
+/// crash mid-write never leaves a truncated session behind
+fn save_session(path: &std::path::Path, session: &SessionFile) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("bin.tmp");
+    let bytes = bincode::serialize(session).map_err(std::io::Error::other)?;
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)
+}
+
+fn load_session(path: &std::path::Path) -> Option<SessionFile> {
+    let bytes = std::fs::read(path).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
+
+/// Number of draw cycles a status message replaces the stats panels for
+const FLASH_CYCLES: u8 = 2;
+const ERROR_FLASH_CYCLES: u8 = 30;
+/// Draw cycles the `[RESTARTED]` flash stays up after the watchdog respawns dcgmi
+const RESTART_FLASH_CYCLES: u8 = 3;
+/// Pause before the watchdog respawns a dead dcgmi
+const WATCHDOG_DELAY: Duration = Duration::from_secs(2);
+/// Screen size used to render the final --export-png frame without a terminal
+const EXPORT_COLUMNS: u16 = 120;
+const EXPORT_ROWS: u16 = 40;
+
+/// Short-lived message shown in place of the stats panels
+struct Flash {
+    message: String,
+    is_error: bool,
+    cycles_left: u8,
+}
+
+/// Every keybinding and what it does, rendered verbatim by the `?` overlay
+const KEYBINDINGS: &[(&str, &str)] = &[
+    ("q", "Quit"),
+    ("?", "Toggle this help"),
+    ("Esc", "Close this help"),
+    ("v", "Toggle bar chart / sparkline view"),
+    ("s", "Cycle bar scaling: sqrt / log / linear"),
+    ("+ / -", "Double / halve the charted time window"),
+    ("o", "Cycle row order: definition / value / name"),
+    ("S", "Save a JSON snapshot of all buffers"),
+    ("P", "Save the screen as a PNG image"),
+    ("↑ / ↓", "Move the metric cursor"),
+    ("Enter", "Open the focused metric's detail view"),
+    ("c", "Overlay a second metric (pick it, then Enter; Esc to stop)"),
+    ("a", "Type an annotation to mark the log (Enter saves, Esc cancels)"),
+    ("Esc / q", "Leave the detail view"),
+    ("t", "Show / hide the NVLink topology (needs --topo)"),
+    ("h", "Show / hide the heatmap of all metrics over time"),
+    ("C", "Show / hide the metric correlation matrix"),
+    ("r", "Show / hide the GPU process panel (needs --show-procs)"),
+    ("PgUp / PgDn", "Scroll the GPU process panel"),
+    ("p", "Freeze / resume the charts (sampling and logging continue)"),
+    ("Tab / Shift-Tab", "Next / previous GPU (switch side with --dual-gpu-compare)"),
+    ("Space", "Pause / resume replay"),
+    ("← / →", "Scroll the charts back / forward in time (step one sample during replay)"),
+];
+
+/// Command-line tips appended to the `?` overlay
+const CLI_HINTS: &[(&str, &str)] = &[
+    ("--percentiles 50,95,99.9", "Choose the percentiles listed in the stats panel"),
+];
+
+/// Which screen the metrics area shows
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AppView {
+    Overview,
+    /// Full-screen chart and statistics for one metric index
+    Detail(usize),
+    /// NVLink adjacency matrix from --topo
+    Topology,
+    /// Every metric as one coloured strip over time
+    Heatmap,
+    /// Pearson r between every pair of metrics over the charted window
+    Correlation,
+}
+
+/// Progress of the `c` overlay of a second metric on a metric's chart
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Compare {
+    /// Metric index whose chart gets the overlay; the cursor picks the other one
+    Picking(usize),
+    /// (charted metric, overlaid metric)
+    Showing(usize, usize),
+}
+
+/// Redraws --benchmark averages over
+const BENCHMARK_DRAWS: usize = 100;
+/// Redraw rates --fps-chart keeps, and the columns and rows of its sparkline
+const FPS_HISTORY: usize = 60;
+const FPS_CHART_WIDTH: u16 = 20;
+const FPS_CHART_HEIGHT: u16 = 3;
+
+/// Highest zoom level, which charts the whole history
+const MAX_ZOOM: u8 = 4;
+/// Samples charted at zoom level 0
+const MIN_ZOOM_SAMPLES: usize = 12;
+
+/// Lines of dcgmi stderr kept for the error screen
+const STDERR_KEEP: usize = 50;
+/// Most recent stderr lines shown above the footer while running
+const STDERR_FOOTER_LINES: usize = 5;
+
+/// Whether data is still flowing
+enum AppState {
+    Running,
+    /// The data source died; the message replaces the metrics until the user quits
+    Error(String),
+}
+
+/// Whether an overlay is drawn on top of the metrics
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AppMode {
+    Normal,
+    HelpOverlay,
+}
+
+/// Which edge of the chart --graph-style anchors the bars to
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GraphStyle {
+    Up,
+    /// Hanging from the top, like a signal level indicator
+    Down,
+}
+
+/// How each metric's history is drawn
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DisplayMode {
+    Bar,
+    Sparkline,
+}
+
+impl DisplayMode {
+    fn toggle(self) -> Self {
+        match self {
+            DisplayMode::Bar => DisplayMode::Sparkline,
+            DisplayMode::Sparkline => DisplayMode::Bar,
+        }
+    }
+}
+
+/// Transform applied to raw values before they become bar heights
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScaleMode {
+    Linear,
+    Sqrt,
+    Log,
+}
+
+impl ScaleMode {
+    fn next(self) -> Self {
+        match self {
+            ScaleMode::Sqrt => ScaleMode::Log,
+            ScaleMode::Log => ScaleMode::Linear,
+            ScaleMode::Linear => ScaleMode::Sqrt,
+        }
+    }
+
+    fn apply(self, val: f64) -> f64 {
+        if val <= 0.0 {
+            return 0.0;
+        }
+        match self {
+            ScaleMode::Linear => val,
+            ScaleMode::Sqrt => val.sqrt(),
+            ScaleMode::Log => val.ln_1p(),
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            ScaleMode::Linear => "[lin]",
+            ScaleMode::Sqrt => "[√]",
+            ScaleMode::Log => "[log]",
+        }
+    }
+}
+
+/// Section a metric row is listed under with --metric-group, in display order
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MetricGroup {
+    Sm,
+    Tensor,
+    Memory,
+    Pcie,
+    Nvlink,
+    Thermal,
+    Clocks,
+    Other,
+}
+
+impl MetricGroup {
+    fn of(field_id: u32) -> MetricGroup {
+        match field_id {
+            1001..=1003 => MetricGroup::Sm,
+            1004 | 1006..=1008 => MetricGroup::Tensor,
+            1005 | 250..=253 => MetricGroup::Memory,
+            1009 | 1010 => MetricGroup::Pcie,
+            1011 | 1012 => MetricGroup::Nvlink,
+            150 | 155 => MetricGroup::Thermal,
+            100 | 101 => MetricGroup::Clocks,
+            _ => MetricGroup::Other,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            MetricGroup::Sm => "SM",
+            MetricGroup::Tensor => "Tensor",
+            MetricGroup::Memory => "Memory",
+            MetricGroup::Pcie => "PCIe",
+            MetricGroup::Nvlink => "NVLink",
+            MetricGroup::Thermal => "Power & Thermal",
+            MetricGroup::Clocks => "Clocks",
+            MetricGroup::Other => "Other",
+        }
+    }
+}
+
+/// Order metric rows are drawn in; buffers keep their definition order regardless
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortMode {
+    Definition,
+    /// Highest most recent sample first
+    Value,
+    Name,
+}
+
+impl SortMode {
+    fn next(self) -> Self {
+        match self {
+            SortMode::Definition => SortMode::Value,
+            SortMode::Value => SortMode::Name,
+            SortMode::Name => SortMode::Definition,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortMode::Definition => "definition",
+            SortMode::Value => "value",
+            SortMode::Name => "name",
+        }
+    }
+}
+
+/// Colours used throughout the UI
+#[derive(Clone, Copy)]
+struct Theme {
+    bar: Color,
+    /// Titles and emphasised values
+    value: Color,
+    /// Secondary text such as stats panels and the header
+    text: Color,
+    dim: Color,
+    border: Color,
+    /// Border of the metric under the cursor
+    focus: Color,
+    alert: Color,
+    /// Badges such as [REPLAY] and [PAUSED]
+    warning: Color,
+    /// Border of derived metrics such as EFFIC
+    derived: Color,
+    /// `Color::Reset` keeps the terminal's own background
+    background: Color,
+}
+
+impl Theme {
+    /// The background, or `fallback` when the theme uses the terminal's own
+    fn background_or(&self, fallback: Color) -> Color {
+        if self.background == Color::Reset { fallback } else { self.background }
+    }
+
+    /// Colours a PNG export uses where the terminal's own would show
+    fn export_colors(&self) -> DefaultColors {
+        DefaultColors { foreground: self.value, background: self.background_or(Color::Black) }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ThemeName {
+    Dark,
+    Light,
+    Solarized,
+}
+
+impl ThemeName {
+    fn theme(self) -> Theme {
+        match self {
+            ThemeName::Dark => Theme {
+                bar: Color::LightGreen,
+                value: Color::White,
+                text: Color::Gray,
+                dim: Color::DarkGray,
+                border: Color::Reset,
+                focus: Color::Cyan,
+                alert: Color::Red,
+                warning: Color::Yellow,
+                derived: Color::Magenta,
+                background: Color::Reset,
+            },
+            ThemeName::Light => Theme {
+                bar: Color::Green,
+                value: Color::Black,
+                text: Color::DarkGray,
+                dim: Color::Gray,
+                border: Color::DarkGray,
+                focus: Color::Blue,
+                alert: Color::Red,
+                warning: Color::Rgb(0xb5, 0x89, 0x00),
+                derived: Color::Magenta,
+                background: Color::White,
+            },
+            ThemeName::Solarized => Theme {
+                bar: Color::Rgb(0x85, 0x99, 0x00),
+                value: Color::Rgb(0x93, 0xa1, 0xa1),
+                text: Color::Rgb(0x83, 0x94, 0x96),
+                dim: Color::Rgb(0x58, 0x6e, 0x75),
+                border: Color::Rgb(0x58, 0x6e, 0x75),
+                focus: Color::Rgb(0x26, 0x8b, 0xd2),
+                alert: Color::Rgb(0xdc, 0x32, 0x2f),
+                warning: Color::Rgb(0xb5, 0x89, 0x00),
+                derived: Color::Rgb(0x6c, 0x71, 0xc4),
+                background: Color::Rgb(0x00, 0x2b, 0x36),
+            },
+        }
+    }
+}
+
+/// Everything the draw loop needs to render a frame
+struct App {
+    /// Monitored (field id, display name) pairs; metric indices refer to this order
+    fields: Vec<(u32, String)>,
+    /// Monitored GPUs and MIG instances, in tab order
+    gpus: Vec<Entity>,
+    history: BTreeMap<Entity, Vec<MetricBuffer>>,
+    history_len: usize,
+    /// Exponential moving average per GPU and metric; `None` until the first sample
+    ema: BTreeMap<Entity, Vec<Option<f64>>>,
+    ema_alpha: f64,
+    /// --smooth: samples averaged into each drawn bar, 1 when off
+    smooth: usize,
+    graph_style: GraphStyle,
+    /// --min-bar-height as a percentage, and the colour of the bars it raises
+    min_bar_height: Option<f64>,
+    min_bar_colour: Option<Color>,
+    /// Most recent samples the stats panel is computed over, from --rolling-window
+    window_samples: usize,
+    active_gpu: usize,
+    mode: AppMode,
+    view: AppView,
+    /// Focused metric row in the overview
+    cursor: usize,
+    /// Samples hidden to the right of the chart window; 0 follows the newest data
+    scroll_offset: usize,
+    /// 0..=MAX_ZOOM; each step doubles the charted window, the top one shows all history
+    zoom_level: u8,
+    /// Sampling interval, used to express the zoom window as time
+    interval_ms: u64,
+    /// Whether --interval-auto may change `interval_ms`, so the header shows it
+    auto_interval: bool,
+    idle: Option<IdleShutdown>,
+    display_mode: DisplayMode,
+    scale_mode: ScaleMode,
+    sort_mode: SortMode,
+    /// --top-n: how many of the highest metrics get a row
+    top_n: Option<usize>,
+    /// --watchface: one line per GPU replaces the whole layout
+    watchface: bool,
+    /// Rows are gathered by `MetricGroup` under divider rows (--metric-group)
+    metric_groups: bool,
+    /// (TX, RX) metric pairs drawn as one stacked row by --stacked-bar
+    stacked: Vec<(usize, usize)>,
+    compare: Option<Compare>,
+    /// (original, alias) metric names from --field-alias, listed in the help overlay
+    aliases: Vec<(String, String)>,
+    /// Screen area of each metric row in the last drawn overview, for mouse clicks
+    last_layout: Vec<(Rect, usize)>,
+    /// Text typed so far after pressing `a`; `None` outside annotation input
+    annotation: Option<String>,
+    /// Annotations confirmed with Enter and not yet written out
+    pending_annotations: Vec<String>,
+    /// Set by `P`; the next drawn frame is exported as a PNG
+    export_requested: bool,
+    theme: Theme,
+    /// Render-time combination of all GPUs from --aggregate
+    aggregate: Option<Aggregate>,
+    /// GPUs (A, B) whose difference A - B is charted instead of a single GPU, from --diff-gpu
+    diff_gpus: Option<(Entity, Entity)>,
+    /// GPUs drawn side by side from --dual-gpu-compare, the one Tab focuses, and how many
+    /// rows each side is scrolled down
+    dual_gpus: Option<(Entity, Entity)>,
+    dual_side: usize,
+    dual_scroll: [usize; 2],
+    /// Percentiles listed in each metric's stats panel
+    percentiles: Vec<f64>,
+    alerts: Vec<AlertRule>,
+    percentile_alarms: Vec<PercentileAlarm>,
+    /// (gpu id, rule index) pairs whose condition currently holds
+    firing: HashSet<(Entity, usize)>,
+    replay: Option<Replay>,
+    /// Metrics shown as per-sample differences (--delta / --delta-metrics)
+    delta: Vec<bool>,
+    /// Raw values of each GPU's previous sample, for delta metrics
+    prev_values: BTreeMap<Entity, Vec<f64>>,
+    /// Shortest accepted gap between two samples of one GPU, from --rate-limit
+    min_sample_gap: Option<Duration>,
+    /// When each GPU's last sample was accepted under --rate-limit
+    last_accepted: BTreeMap<Entity, Instant>,
+    /// Samples discarded for arriving early
+    dropped: u64,
+    /// Values per dcgmi line, and the columns each metric sums (--combine-nvlink/--combine-pcie);
+    /// empty `columns` means every metric is its own column
+    raw_field_count: usize,
+    columns: Vec<Vec<usize>>,
+    /// Set when EFFIC is shown, as the last metric
+    efficiency: Option<Efficiency>,
+    /// Durations of the latest redraws, newest last; `Some` only with --benchmark
+    draw_times: Option<VecDeque<Duration>>,
+    /// Frames per second between consecutive redraws, newest last; `Some` only with --fps-chart
+    fps_history: Option<VecDeque<f64>>,
+    last_draw: Option<Instant>,
+    /// `GPU ...` lines that did not parse, in total and since the last good one
+    parse_errors: u64,
+    consecutive_parse_errors: u64,
+    /// Whether the footer always shows the parse error count (--error-threshold)
+    show_parse_errors: bool,
+    /// Latest process list per GPU id; `Some` only with --show-procs
+    processes: Option<BTreeMap<usize, Vec<GpuProcess>>>,
+    /// Position of each metric under --field-order, used by the definition sort order
+    field_order: Option<Vec<usize>>,
+    /// Whether the process panel is shown (`r`) and how many rows it is scrolled down
+    show_processes: bool,
+    process_scroll: usize,
+    /// Traffic-light levels per metric; `None` keeps the plain bar colour
+    thresholds: Vec<Option<Thresholds>>,
+    /// --temp-ranges bands applied to every temperature metric instead of its thresholds
+    temp_ranges: Vec<TempRange>,
+    /// Per-metric chart ceiling from --max-value; `None` scales to the tallest bar
+    max_values: Vec<Option<f64>>,
+    /// --metric-scale factor per measured metric, applied as samples are parsed; 1.0 for
+    /// metrics it does not name
+    scales: Vec<f64>,
+    /// --nvlink-lanes: NVLink rows are divided by this for display only; logged values stay raw
+    nvlink_lanes: Option<u32>,
+    /// --metric-unit label per metric, replacing the field kind's formatting in the stats panel
+    units: Vec<Option<String>>,
+    /// Per-metric p50 of the --baseline-file run
+    baseline: Option<Vec<f64>>,
+    /// Allowed deviation of the live p50 from the baseline, in percent
+    baseline_tolerance: f64,
+    /// Accept entities not listed up front (--mig and --group-id report them as they appear)
+    discover_entities: bool,
+    /// Entities seeded from --gpus that dcgmi has not reported yet; dropped on the first discovery
+    placeholders: HashSet<Entity>,
+    /// DCGM group being watched, which adds a row with the group mean
+    group_id: Option<u32>,
+    /// Model names from --gpu-name; GPUs missing here are shown by id only
+    gpu_names: BTreeMap<usize, String>,
+    /// Result of the startup `dcgmi topo` query; `None` without --topo
+    topology: Option<Result<Topology, String>>,
+    flash: Option<Flash>,
+    started: Instant,
+    /// --silent-start: how long after `started` the charts stay hidden
+    silent_start: Option<Duration>,
+    /// When a sample line last parsed, for --warn-on-missing-samples
+    last_sample_time: Instant,
+    warn_missing: bool,
+    /// Session length from --duration, after which the app quits
+    duration: Option<Duration>,
+    /// Parsed samples to collect before quitting, from --sample-count
+    sample_limit: Option<u64>,
+    samples_seen: u64,
+    /// Process from --watch-pid whose exit ends the session
+    watch_pid: Option<u32>,
+    /// Arrival times of samples within the last second, for the header's rate
+    recent_samples: VecDeque<Instant>,
+    is_paused: bool,
+    /// The --input-file source has been read to the end
+    at_eof: bool,
+    /// Copy of the history taken when `p` froze the display
+    paused_snapshot: Option<BTreeMap<Entity, Vec<MetricBuffer>>>,
+    /// Most recent line read from dcgmi, parsed or not, shown in the footer
+    last_raw_line: String,
+    stderr_lines: VecDeque<String>,
+    /// Show the latest stderr lines in the footer; off when they go to --stderr-log
+    stderr_in_footer: bool,
+    state: AppState,
+}
+
+impl App {
+    fn new(fields: Vec<(u32, String)>, gpus: Vec<Entity>, history_len: usize) -> Self {
+        let history_len = history_len.max(1);
+        let history = gpus.iter()
+            .map(|&id| (id, vec![VecDeque::with_capacity(history_len); fields.len()]))
+            .collect();
+        let ema = gpus.iter().map(|&id| (id, vec![None; fields.len()])).collect();
+        App {
+            raw_field_count: fields.len(),
+            columns: Vec::new(),
+            efficiency: None,
+            fields,
+            gpus,
+            history,
+            history_len,
+            ema,
+            ema_alpha: 0.1,
+            smooth: 1,
+            graph_style: GraphStyle::Up,
+            min_bar_height: None,
+            min_bar_colour: None,
+            window_samples: usize::MAX,
+            active_gpu: 0,
+            mode: AppMode::Normal,
+            view: AppView::Overview,
+            cursor: 0,
+            scroll_offset: 0,
+            zoom_level: MAX_ZOOM,
+            interval_ms: 100,
+            auto_interval: false,
+            idle: None,
+            display_mode: DisplayMode::Bar,
+            scale_mode: ScaleMode::Sqrt,
+            sort_mode: SortMode::Definition,
+            top_n: None,
+            watchface: false,
+            metric_groups: false,
+            stacked: Vec::new(),
+            compare: None,
+            aliases: Vec::new(),
+            last_layout: Vec::new(),
+            annotation: None,
+            pending_annotations: Vec::new(),
+            export_requested: false,
+            theme: ThemeName::Dark.theme(),
+            aggregate: None,
+            diff_gpus: None,
+            dual_gpus: None,
+            dual_side: 0,
+            dual_scroll: [0; 2],
+            percentiles: vec![50.0, 90.0, 99.0],
+            alerts: Vec::new(),
+            percentile_alarms: Vec::new(),
+            firing: HashSet::new(),
+            replay: None,
+            delta: Vec::new(),
+            prev_values: BTreeMap::new(),
+            min_sample_gap: None,
+            last_accepted: BTreeMap::new(),
+            dropped: 0,
+            draw_times: None,
+            fps_history: None,
+            last_draw: None,
+            parse_errors: 0,
+            consecutive_parse_errors: 0,
+            show_parse_errors: false,
+            processes: None,
+            show_processes: false,
+            process_scroll: 0,
+            field_order: None,
+            thresholds: Vec::new(),
+            temp_ranges: Vec::new(),
+            max_values: Vec::new(),
+            scales: Vec::new(),
+            nvlink_lanes: None,
+            units: Vec::new(),
+            baseline: None,
+            baseline_tolerance: 10.0,
+            discover_entities: false,
+            placeholders: HashSet::new(),
+            group_id: None,
+            gpu_names: BTreeMap::new(),
+            topology: None,
+            flash: None,
+            started: Instant::now(),
+            silent_start: None,
+            last_sample_time: Instant::now(),
+            warn_missing: false,
+            duration: None,
+            sample_limit: None,
+            samples_seen: 0,
+            watch_pid: None,
+            recent_samples: VecDeque::new(),
+            is_paused: false,
+            at_eof: false,
+            paused_snapshot: None,
+            last_raw_line: String::new(),
+            stderr_lines: VecDeque::new(),
+            stderr_in_footer: true,
+            state: AppState::Running,
+        }
+    }
+
+    fn flash(&mut self, message: String, is_error: bool) {
+        let cycles_left = if is_error { ERROR_FLASH_CYCLES } else { FLASH_CYCLES };
+        self.flash = Some(Flash { message, is_error, cycles_left });
+    }
+
+    /// Counts down the flash message once per draw cycle
+    fn tick_flash(&mut self) {
+        if let Some(ref mut flash) = self.flash {
+            flash.cycles_left = flash.cycles_left.saturating_sub(1);
+            if flash.cycles_left == 0 {
+                self.flash = None;
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let gpus = self.history.iter().map(|(entity, buffers)| GpuSnapshot {
+            gpu: entity.gpu,
+            mig: entity.instance,
+            metrics: self.fields.iter().zip(buffers).map(|((_, name), buf)| {
+                let stats = buffer_percentiles(buf, &[50.0, 90.0, 99.0]);
+                MetricSnapshot {
+                    name: name.to_string(),
+                    p50: stats[0],
+                    p90: stats[1],
+                    p99: stats[2],
+                    values: buf.iter().copied().collect(),
+                }
+            }).collect(),
+        }).collect();
+        Snapshot {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            gpus,
+        }
+    }
+
+    /// Statistics over each GPU's whole history, skipping metrics that never got a sample
+    fn summary(&self) -> Vec<SummaryRow> {
+        let mut rows = Vec::new();
+        for (entity, buffers) in &self.history {
+            for ((_, name), buf) in self.fields.iter().zip(buffers).filter(|(_, buf)| !buf.is_empty()) {
+                let stats = buffer_percentiles(buf, &[50.0, 90.0, 99.0]);
+                rows.push(SummaryRow {
+                    gpu: entity.to_string(),
+                    metric: name.to_string(),
+                    min: buf.iter().copied().fold(f64::INFINITY, f64::min),
+                    mean: buf.iter().sum::<f64>() / buf.len() as f64,
+                    p50: stats[0],
+                    p90: stats[1],
+                    p99: stats[2],
+                    max: buf.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                });
+            }
+        }
+        rows
+    }
+
+    /// Writes the snapshot to `dcgmi_snapshot_<timestamp>.json` in the working directory
+    fn save_snapshot(&self) -> std::io::Result<String> {
+        let path = format!("dcgmi_snapshot_{}.json", chrono::Local::now().format("%Y%m%d_%H%M%S"));
+        let file = File::create(&path)?;
+        serde_json::to_writer_pretty(file, &self.snapshot())?;
+        Ok(path)
+    }
+
+    /// Writes the snapshot to `<dir>/checkpoint_<unix_ts>.json`, then deletes all but the
+    /// newest `keep` checkpoints there
+    fn save_checkpoint(&self, dir: &str, keep: Option<usize>) -> std::io::Result<String> {
+        let path = format!("{}/checkpoint_{}.json", dir, chrono::Local::now().timestamp());
+        let file = File::create(&path)?;
+        serde_json::to_writer_pretty(file, &self.snapshot())?;
+        if let Some(keep) = keep {
+            let mut checkpoints: Vec<(i64, std::path::PathBuf)> = std::fs::read_dir(dir)?
+                .filter_map(|entry| {
+                    let path = entry.ok()?.path();
+                    let ts = path.file_name()?.to_str()?.strip_prefix("checkpoint_")?.strip_suffix(".json")?.parse().ok()?;
+                    Some((ts, path))
+                })
+                .collect();
+            checkpoints.sort();
+            for (_, old) in checkpoints.iter().rev().skip(keep) {
+                std::fs::remove_file(old)?;
+            }
+        }
+        Ok(path)
+    }
+
+    /// Pre-populates buffers from a previous session recorded with the same fields
+    fn restore_session(&mut self, session: SessionFile) {
+        if session.fields != self.fields {
+            return;
+        }
+        for (gpu_id, buffers) in session.history {
+            let Some(current) = self.history.get_mut(&gpu_id) else {
+                continue;
+            };
+            for (buf, saved) in current.iter_mut().zip(buffers) {
+                buf.extend(saved);
+                while buf.len() > self.history_len {
+                    buf.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Most recent displayed value of a metric, combined across GPUs in aggregate mode
+    fn latest_value(&self, metric: usize) -> f64 {
+        let history = self.displayed_history();
+        match self.aggregate {
+            Some(aggregate) => {
+                let values: Vec<f64> = aggregated_values(history).filter_map(|buffers| buffers[metric].back().copied()).collect();
+                if values.is_empty() { 0.0 } else { aggregate.apply(&values) }
+            }
+            None => history[&self.gpus[self.active_gpu]][metric].back().copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Metric indices in the order their rows are drawn
+    fn display_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.fields.len()).collect();
+        if let Some(n) = self.top_n.filter(|&n| n < order.len()) {
+            let latest: Vec<f64> = order.iter().map(|&i| self.latest_value(i)).collect();
+            let mut ranked = order.clone();
+            ranked.sort_by(|&a, &b| latest[b].total_cmp(&latest[a]));
+            ranked.truncate(n);
+            order.retain(|i| ranked.contains(i));
+        }
+        match self.sort_mode {
+            SortMode::Definition => {
+                if let Some(ref rank) = self.field_order {
+                    order.sort_by_key(|&i| rank[i]);
+                }
+            }
+            SortMode::Value => {
+                let latest: Vec<f64> = order.iter().map(|&i| self.latest_value(i)).collect();
+                order.sort_by(|&a, &b| latest[b].total_cmp(&latest[a]));
+            }
+            SortMode::Name => order.sort_by(|&a, &b| self.fields[a].1.cmp(&self.fields[b].1)),
+        }
+        // Stable, so the sort mode still orders rows within each group
+        if self.metric_groups {
+            order.sort_by_key(|&i| MetricGroup::of(self.fields[i].0));
+        }
+        order.sort_by_key(|&i| self.is_derived(i));
+        order
+    }
+
+    /// `display_order` without the RX metrics --stacked-bar draws inside their TX rows
+    fn row_order(&self) -> Vec<usize> {
+        let mut order = self.display_order();
+        order.retain(|&metric| !self.stacked.iter().any(|&(_, rx)| rx == metric));
+        order
+    }
+
+    /// RX metric stacked onto `metric`'s chart, when `metric` is a --stacked-bar TX
+    fn stacked_rx(&self, metric: usize) -> Option<usize> {
+        self.stacked.iter().find(|&&(tx, _)| tx == metric).map(|&(_, rx)| rx)
+    }
+
+    /// GPU of the --dual-gpu-compare side Tab has focused; detail and heatmap views show it
+    fn dual_focus(&self) -> Option<Entity> {
+        self.dual_gpus.map(|(a, b)| if self.dual_side == 0 { a } else { b })
+    }
+
+    /// Scrolls the focused --dual-gpu-compare side one row, moving the cursor to its top row
+    fn scroll_dual(&mut self, down: bool) {
+        let order = self.row_order();
+        let scroll = &mut self.dual_scroll[self.dual_side];
+        *scroll = if down { (*scroll + 1).min(order.len().saturating_sub(1)) } else { scroll.saturating_sub(1) };
+        self.cursor = order[*scroll];
+    }
+
+    /// Moves the metric cursor in display order, carrying the detail view along with it
+    fn move_cursor(&mut self, down: bool) {
+        let order = self.row_order();
+        let count = order.len();
+        let pos = order.iter().position(|&i| i == self.cursor).unwrap_or(0);
+        let pos = if down { (pos + 1) % count } else { (pos + count - 1) % count };
+        self.cursor = order[pos];
+        if let AppView::Detail(_) = self.view {
+            self.view = AppView::Detail(self.cursor);
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        self.is_paused = !self.is_paused;
+        self.paused_snapshot = self.is_paused.then(|| self.history.clone());
+    }
+
+    /// The buffers the charts should show: frozen while paused, live otherwise
+    fn displayed_history(&self) -> &BTreeMap<Entity, Vec<MetricBuffer>> {
+        self.paused_snapshot.as_ref().unwrap_or(&self.history)
+    }
+
+    fn clear_history(&mut self) {
+        for buffers in self.history.values_mut() {
+            buffers.iter_mut().for_each(|buf| buf.clear());
+        }
+    }
+
+    /// Buffer positions `start..end` shown by a chart that fits `fits` samples
+    fn chart_range(&self, len: usize, fits: usize) -> (usize, usize) {
+        let visible = fits.min(self.zoom_window());
+        let end = len.saturating_sub(self.scroll_offset).max(visible.min(len));
+        (end.saturating_sub(visible), end)
+    }
+
+    /// Samples charted at the current zoom level
+    fn zoom_window(&self) -> usize {
+        if self.zoom_level >= MAX_ZOOM {
+            self.history_len
+        } else {
+            (MIN_ZOOM_SAMPLES << self.zoom_level).min(self.history_len)
+        }
+    }
+
+    fn zoom(&mut self, out: bool) {
+        self.zoom_level = if out { (self.zoom_level + 1).min(MAX_ZOOM) } else { self.zoom_level.saturating_sub(1) };
+    }
+
+    /// Shifts the chart window; scrolling right back to the newest sample resumes following it
+    fn scroll(&mut self, back: bool) {
+        const SCROLL_STEP: usize = 5;
+        self.scroll_offset = if back {
+            (self.scroll_offset + SCROLL_STEP).min(self.history_len)
+        } else {
+            self.scroll_offset.saturating_sub(SCROLL_STEP)
+        };
+    }
+
+    /// Feeds replay rows that are due into the history
+    fn advance_replay(&mut self) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        let due = replay.advance();
+        let rows = std::mem::take(&mut replay.rows);
+        for row in &rows[due] {
+            self.push_sample(row.gpu_id, &row.values);
+        }
+        self.replay.as_mut().unwrap().rows = rows;
+    }
+
+    /// Steps the replay one row and rebuilds the history up to the new position
+    fn step_replay(&mut self, forward: bool) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        if !replay.step(forward) {
+            return;
+        }
+        let pos = replay.pos;
+        let rows = std::mem::take(&mut replay.rows);
+        self.clear_history();
+        for row in &rows[..pos] {
+            self.push_sample(row.gpu_id, &row.values);
+        }
+        self.replay.as_mut().unwrap().rows = rows;
+    }
+
+    /// Re-evaluates every alert rule against the latest samples and returns the
+    /// (entity, rule index) pairs that started firing since the last call.
+    fn evaluate_alerts(&mut self) -> Vec<(Entity, usize)> {
+        let mut newly_fired = Vec::new();
+        for (&gpu_id, buffers) in &self.history {
+            for (idx, rule) in self.alerts.iter().enumerate() {
+                let active = buffers[rule.metric].back().is_some_and(|v| rule.matches(*v));
+                if active {
+                    if self.firing.insert((gpu_id, idx)) {
+                        newly_fired.push((gpu_id, idx));
+                    }
+                } else {
+                    self.firing.remove(&(gpu_id, idx));
+                }
+            }
+        }
+        newly_fired
+    }
+
+    /// Advances every percentile alarm by one tick and returns the (entity, rule index,
+    /// percentile) of those that should ring now
+    fn evaluate_percentile_alarms(&mut self) -> Vec<(Entity, usize, f64)> {
+        let mut rung = Vec::new();
+        for (idx, alarm) in self.percentile_alarms.iter_mut().enumerate() {
+            for (&gpu_id, buffers) in &self.history {
+                let window = &buffers[alarm.metric];
+                let window: MetricBuffer = window.range(window.len().saturating_sub(self.window_samples)..).copied().collect();
+                let value = buffer_percentiles(&window, &[alarm.pct])[0];
+                let (over, armed) = alarm.state.entry(gpu_id).or_insert((0, true));
+                *over = if value > alarm.threshold { *over + 1 } else { 0 };
+                if value < alarm.threshold * ALARM_REARM {
+                    *armed = true;
+                }
+                if *armed && *over >= ALARM_TICKS {
+                    *armed = false;
+                    alarm.bell_count += 1;
+                    rung.push((gpu_id, idx, value));
+                }
+            }
+        }
+        rung
+    }
+
+    fn is_alerting(&self, gpu_id: Entity, metric: usize) -> bool {
+        self.alerts.iter().enumerate()
+            .any(|(idx, rule)| rule.metric == metric && self.firing.contains(&(gpu_id, idx)))
+    }
+
+    fn baseline_p50(&self, metric: usize) -> Option<f64> {
+        self.baseline.as_ref()?.get(metric).copied()
+    }
+
+    /// Whether the live p50 of `buf` is further than the tolerance from the baseline p50
+    fn deviates_from_baseline(&self, metric: usize, buf: &MetricBuffer) -> bool {
+        let Some(base) = self.baseline_p50(metric) else {
+            return false;
+        };
+        let live = buffer_percentiles(&self.stats_window(buf), &[50.0])[0];
+        if base == 0.0 {
+            return live != 0.0;
+        }
+        ((live - base) / base).abs() * 100.0 > self.baseline_tolerance
+    }
+
+    /// `value` in the metric's --metric-unit if it has one, otherwise in its field's unit
+    fn format_value(&self, metric: usize, value: f64) -> String {
+        match self.units.get(metric).and_then(Option::as_deref) {
+            Some(unit) => format!("{} {}", format_metric_value(MetricKind::Plain, value), unit),
+            None => format_metric_value(metric_kind(self.fields[metric].0), value),
+        }
+    }
+
+    /// The metric's --max-value ceiling, if it has one
+    fn max_value(&self, metric: usize) -> Option<f64> {
+        self.max_values.get(metric).copied().flatten()
+    }
+
+    /// How long the stream has been silent, once that passes three sample intervals
+    /// (--warn-on-missing-samples); a finished input file is not a gap
+    fn stale_for(&self) -> Option<Duration> {
+        let silent = self.last_sample_time.elapsed();
+        (self.warn_missing && !self.at_eof && silent > Duration::from_millis(3 * self.interval_ms)).then_some(silent)
+    }
+
+    /// Whether the latest value is above the metric's --max-value ceiling
+    fn is_clamped(&self, metric: usize, buf: &MetricBuffer) -> bool {
+        matches!((self.max_value(metric), buf.back()), (Some(max), Some(&latest)) if latest > max)
+    }
+
+    /// Bar colour for a buffer whose latest value is compared against the metric's thresholds
+    fn threshold_color(&self, metric: usize, buf: &MetricBuffer) -> Color {
+        if let Some(range) = self.temp_range(metric, buf) {
+            return range.color;
+        }
+        let (Some(Some(levels)), Some(&latest)) = (self.thresholds.get(metric), buf.back()) else {
+            return self.theme.bar;
+        };
+        if latest >= levels.critical {
+            self.theme.alert
+        } else if latest >= levels.warn {
+            self.theme.warning
+        } else {
+            self.theme.bar
+        }
+    }
+
+    /// The --temp-ranges band the latest value of a temperature metric falls in
+    fn temp_range(&self, metric: usize, buf: &MetricBuffer) -> Option<&TempRange> {
+        if metric_kind(self.fields[metric].0) != MetricKind::Celsius {
+            return None;
+        }
+        let latest = *buf.back()?;
+        self.temp_ranges.iter().find(|range| range.low <= latest && latest < range.high)
+    }
+
+    /// Whether the row for `metric` should be drawn in the alert colour
+    fn row_alerting(&self, metric: usize) -> bool {
+        match self.aggregate {
+            Some(_) => self.gpus.iter().any(|&gpu_id| self.is_alerting(gpu_id, metric)),
+            None => self.is_alerting(self.gpus[self.active_gpu], metric),
+        }
+    }
+
+    /// Appends one sample to a GPU's buffers. Returns false for GPUs that are not being monitored.
+    fn push_sample(&mut self, gpu_id: Entity, raw: &[f64]) -> bool {
+        let with_derived;
+        let raw = if raw.len() < self.fields.len() {
+            with_derived = self.with_derived(gpu_id, raw);
+            &with_derived[..]
+        } else {
+            raw
+        };
+        if !self.history.contains_key(&gpu_id) {
+            if !self.discover_entities {
+                return false;
+            }
+            self.add_entity(gpu_id);
+        }
+        self.placeholders.remove(&gpu_id);
+        let Some(vals) = self.apply_delta(gpu_id, raw) else {
+            return true;
+        };
+        let vals = &vals[..];
+        let gpu_history = self.history.get_mut(&gpu_id).unwrap();
+        for (i, val) in vals.iter().enumerate() {
+            let buf = &mut gpu_history[i];
+            // `while` rather than `if` so buffers longer than the current depth shrink down to it
+            while buf.len() >= self.history_len {
+                buf.pop_front();
+            }
+            buf.push_back(*val);
+        }
+        // Keep a scrolled-back window on the same samples while new ones arrive
+        let shown = self.aggregate.is_some() || self.diff_gpus.is_some() || self.dual_gpus.is_some() || self.gpus[self.active_gpu] == gpu_id;
+        if self.scroll_offset > 0 && shown && !self.is_paused {
+            self.scroll_offset = (self.scroll_offset + 1).min(self.history_len);
+        }
+        if let Some(emas) = self.ema.get_mut(&gpu_id) {
+            for (ema, val) in emas.iter_mut().zip(vals) {
+                // Seeding with the first sample avoids a slow climb up from zero
+                *ema = Some(match *ema {
+                    Some(prev) => self.ema_alpha * val + (1.0 - self.ema_alpha) * prev,
+                    None => *val,
+                });
+            }
+        }
+        let now = Instant::now();
+        self.recent_samples.push_back(now);
+        while self.recent_samples.front().is_some_and(|t| now.duration_since(*t) > Duration::from_secs(1)) {
+            self.recent_samples.pop_front();
+        }
+        true
+    }
+
+    /// Starts tracking an entity first seen in dcgmi output, keeping the tab that is
+    /// currently shown selected. Seeded entities that never produced a sample are dropped,
+    /// since they were only placeholders until dcgmi reported which GPUs it watches. A paused
+    /// snapshot gets empty buffers for the new tab so it can be drawn until unpaused.
+    fn add_entity(&mut self, entity: Entity) {
+        let active = self.gpus.get(self.active_gpu).copied();
+        for placeholder in std::mem::take(&mut self.placeholders) {
+            self.gpus.retain(|e| *e != placeholder);
+            self.history.remove(&placeholder);
+            self.ema.remove(&placeholder);
+        }
+        self.history.insert(entity, vec![VecDeque::with_capacity(self.history_len); self.fields.len()]);
+        self.ema.insert(entity, vec![None; self.fields.len()]);
+        if let Some(snapshot) = self.paused_snapshot.as_mut() {
+            snapshot.insert(entity, vec![MetricBuffer::new(); self.fields.len()]);
+        }
+        self.gpus.push(entity);
+        self.gpus.sort();
+        self.active_gpu = active.and_then(|a| self.gpus.iter().position(|&e| e == a)).unwrap_or(0);
+    }
+
+    /// Replaces --delta metrics with the change since the GPU's previous sample. Returns
+    /// `None` for a GPU's first sample, which only primes the previous values.
+    fn apply_delta(&mut self, gpu_id: Entity, raw: &[f64]) -> Option<Vec<f64>> {
+        if !self.delta.contains(&true) {
+            return Some(raw.to_vec());
+        }
+        let prev = self.prev_values.insert(gpu_id, raw.to_vec())?;
+        Some(raw.iter().zip(&prev).zip(&self.delta)
+            .map(|((val, prev), &delta)| if delta { val - prev } else { *val })
+            .collect())
+    }
+
+    /// Handles one stdout line from dcgmi, returning the sample if it was accepted
+    fn ingest_line(&mut self, line: &str) -> Option<(Entity, Vec<f64>)> {
+        self.last_raw_line = line.to_string();
+        let Some((gpu_id, mut vals)) = parse_metric_line(line, self.raw_field_count) else {
+            if !line.trim().is_empty() {
+                self.parse_errors += 1;
+                self.consecutive_parse_errors += 1;
+            }
+            return None;
+        };
+        self.consecutive_parse_errors = 0;
+        self.last_sample_time = Instant::now();
+        if !self.columns.is_empty() {
+            vals = self.columns.iter().map(|columns| columns.iter().map(|&i| vals[i]).sum()).collect();
+        }
+        for (val, factor) in vals.iter_mut().zip(&self.scales) {
+            *val *= factor;
+        }
+        if !self.within_rate_limit(gpu_id) {
+            self.dropped += 1;
+            return None;
+        }
+        if !self.push_sample(gpu_id, &vals) {
+            return None;
+        }
+        self.samples_seen += 1;
+        Some((gpu_id, vals))
+    }
+
+    /// `raw` (the measured metrics) followed by the derived metrics computed from it
+    fn with_derived(&self, gpu_id: Entity, raw: &[f64]) -> Vec<f64> {
+        let mut values = raw.to_vec();
+        if let Some(ref efficiency) = self.efficiency {
+            // EFFIC divides FB_USED by the total in MB, so --metric-scale is undone first
+            let unscaled: Vec<f64> = raw.iter().enumerate().map(|(i, val)| val / self.scales.get(i).unwrap_or(&1.0)).collect();
+            values.push(efficiency.value(gpu_id, &unscaled));
+        }
+        values
+    }
+
+    /// Records how long a redraw took for --benchmark
+    fn record_draw(&mut self, elapsed: Duration) {
+        if let Some(ref mut times) = self.draw_times {
+            if times.len() >= BENCHMARK_DRAWS {
+                times.pop_front();
+            }
+            times.push_back(elapsed);
+        }
+    }
+
+    /// Records the rate implied by the gap since the previous redraw for --fps-chart
+    fn record_fps(&mut self) {
+        let now = Instant::now();
+        if let (Some(history), Some(last)) = (self.fps_history.as_mut(), self.last_draw) {
+            if history.len() >= FPS_HISTORY {
+                history.pop_front();
+            }
+            history.push_back(1.0 / now.duration_since(last).as_secs_f64().max(1e-6));
+        }
+        self.last_draw = Some(now);
+    }
+
+    /// Mean of the recorded redraw times, once there is at least one
+    fn average_draw_time(&self) -> Option<Duration> {
+        let times = self.draw_times.as_ref().filter(|times| !times.is_empty())?;
+        Some(times.iter().sum::<Duration>() / times.len() as u32)
+    }
+
+    /// Time left of --silent-start, `None` once it is over or `samples` fill the history
+    fn collecting_for(&self, samples: usize) -> Option<Duration> {
+        let wait = self.silent_start?.checked_sub(self.started.elapsed())?;
+        (samples < self.history_len).then_some(wait)
+    }
+
+    /// Whether `metric` is an NVLink row that --nvlink-lanes divides into a per-lane rate
+    fn per_lane(&self, metric: usize) -> bool {
+        self.nvlink_lanes.is_some() && matches!(self.fields[metric].0, 1011 | 1012) && !self.is_derived(metric)
+    }
+
+    /// `value` of `metric` as charted: divided by --nvlink-lanes on the NVLink rows
+    fn per_lane_value(&self, metric: usize, value: f64) -> f64 {
+        match self.nvlink_lanes {
+            Some(lanes) if self.per_lane(metric) => value / lanes as f64,
+            _ => value,
+        }
+    }
+
+    /// `history` as charted, with the NVLink rows divided into per-lane rates. The buffers
+    /// themselves stay raw so alerts, exports and the topology view see link totals.
+    fn per_lane_history<'a>(&self, history: &'a [MetricBuffer]) -> Cow<'a, [MetricBuffer]> {
+        if self.nvlink_lanes.is_none() {
+            return Cow::Borrowed(history);
+        }
+        Cow::Owned(history.iter().enumerate().map(|(metric, buf)| buf.iter().map(|val| self.per_lane_value(metric, *val)).collect()).collect())
+    }
+
+    /// Whether derived metrics such as EFFIC are computed rather than measured
+    fn is_derived(&self, metric: usize) -> bool {
+        self.efficiency.is_some() && metric == self.fields.len() - 1
+    }
+
+    /// Whether a sample for `gpu_id` arriving now is far enough behind the last accepted one
+    fn within_rate_limit(&mut self, gpu_id: Entity) -> bool {
+        let Some(gap) = self.min_sample_gap else {
+            return true;
+        };
+        let now = Instant::now();
+        if self.last_accepted.get(&gpu_id).is_some_and(|last| now.duration_since(*last) < gap) {
+            return false;
+        }
+        self.last_accepted.insert(gpu_id, now);
+        true
+    }
+
+    /// Whether --duration or --sample-count says the run is over
+    fn limit_reached(&self) -> bool {
+        self.duration.is_some_and(|d| self.started.elapsed() >= d)
+            || self.sample_limit.is_some_and(|n| self.samples_seen >= n)
+    }
+
+    /// Processes of the active tab's GPU while the --show-procs panel is open
+    fn shown_processes(&self) -> Option<&[GpuProcess]> {
+        let processes = self.processes.as_ref().filter(|_| self.show_processes)?;
+        Some(processes.get(&self.gpus.get(self.active_gpu)?.gpu).map_or(&[], Vec::as_slice))
+    }
+
+    /// Stderr lines to show above the raw dcgmi line in the footer
+    fn footer_stderr(&self) -> Vec<&str> {
+        if !self.stderr_in_footer {
+            return Vec::new();
+        }
+        let skip = self.stderr_lines.len().saturating_sub(STDERR_FOOTER_LINES);
+        self.stderr_lines.iter().skip(skip).map(String::as_str).collect()
+    }
+
+    fn push_stderr(&mut self, line: String) {
+        if self.stderr_lines.len() >= STDERR_KEEP {
+            self.stderr_lines.pop_front();
+        }
+        self.stderr_lines.push_back(line);
+    }
+
+    /// The metric overlaid on `metric`'s chart in compare mode
+    fn compare_overlay(&self, metric: usize) -> Option<usize> {
+        match self.compare {
+            Some(Compare::Showing(charted, overlaid)) if charted == metric => Some(overlaid),
+            _ => None,
+        }
+    }
+
+    /// EMA of a metric on the GPU currently shown
+    fn ema_of(&self, metric: usize) -> Option<f64> {
+        if let Some(aggregate) = self.aggregate {
+            let values: Vec<f64> = aggregated_values(&self.ema).filter_map(|ema| ema.get(metric).copied().flatten()).collect();
+            return (!values.is_empty()).then(|| aggregate.apply(&values));
+        }
+        self.ema.get(&self.gpus[self.active_gpu])?.get(metric).copied().flatten()
+    }
+
+    /// Copies the latest value, p50 and p90 of every buffer into the shared state
+    fn publish_latest(&self, state: &Mutex<LatestStats>) {
+        let mut state = state.lock().unwrap();
+        if state.names.is_empty() {
+            state.names = self.fields.iter().map(|(_, name)| name.clone()).collect();
+        }
+        state.gpus = self.history.iter().map(|(&entity, buffers)| {
+            let values = buffers.iter().map(|buf| {
+                let pcts = buffer_percentiles(&self.stats_window(buf), &[50.0, 90.0]);
+                [buf.back().copied().unwrap_or(0.0), pcts[0], pcts[1]]
+            }).collect();
+            (entity, values)
+        }).collect();
+    }
+
+    /// The tail of `buf` that statistics are computed over
+    fn stats_window(&self, buf: &MetricBuffer) -> MetricBuffer {
+        buf.range(buf.len().saturating_sub(self.window_samples)..).copied().collect()
+    }
+
+    /// Samples accepted over the last second
+    fn sample_rate(&self) -> usize {
+        let now = Instant::now();
+        self.recent_samples.iter().filter(|t| now.duration_since(**t) <= Duration::from_secs(1)).count()
+    }
+}
+
+fn draw_header(f: &mut Frame, area: Rect, app: &App) {
+    let theme = app.theme;
+    let mut spans = Vec::new();
+    if let Some(name) = app.gpu_names.get(&app.gpus[app.active_gpu].gpu).filter(|_| app.aggregate.is_none() && app.diff_gpus.is_none() && app.dual_gpus.is_none()) {
+        spans.push(Span::styled(format!("{}  ", name), Style::default().fg(theme.value).add_modifier(Modifier::BOLD)));
+    }
+    if let Some(ref replay) = app.replay {
+        spans.push(Span::styled("[REPLAY]", Style::default().fg(theme.background_or(Color::Black)).bg(theme.warning).add_modifier(Modifier::BOLD)));
+        spans.push(Span::raw(format!(" {} / {}  {}x", format_duration(replay.position.min(replay.total())), format_duration(replay.total()), replay.speed)));
+        if replay.paused {
+            spans.push(Span::styled("  paused", Style::default().fg(theme.warning)));
+        }
+        spans.push(Span::raw("  "));
+    }
+    if let Some((a, b)) = app.diff_gpus {
+        spans.push(Span::styled(format!("[GPU {} - GPU {}]", a, b), Style::default().fg(theme.background_or(Color::Black)).bg(theme.focus).add_modifier(Modifier::BOLD)));
+        spans.push(Span::raw("  "));
+    }
+    if let Some(aggregate) = app.aggregate {
+        spans.push(Span::styled(format!("[{} of {} GPUs]", aggregate.label(), app.gpus.len()), Style::default().fg(theme.background_or(Color::Black)).bg(theme.focus).add_modifier(Modifier::BOLD)));
+        spans.push(Span::raw("  "));
+    }
+    if app.at_eof {
+        spans.push(Span::styled("[EOF]", Style::default().fg(theme.background_or(Color::Black)).bg(theme.warning).add_modifier(Modifier::BOLD)));
+        spans.push(Span::raw("  "));
+    }
+    spans.push(Span::styled(chrono::Local::now().format("%H:%M:%S").to_string(), Style::default().add_modifier(Modifier::BOLD)));
+    spans.push(Span::raw(format!("  up {}  {} samples/s", format_duration(app.started.elapsed()), app.sample_rate())));
+    if app.auto_interval {
+        spans.push(Span::styled(format!("  [interval {}ms]", app.interval_ms), Style::default().fg(theme.focus)));
+    }
+    if let Some(left) = app.idle.as_ref().and_then(IdleShutdown::remaining) {
+        spans.push(Span::styled(format!("  [idle: exit in {}s]", left.as_secs()), Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)));
+    }
+    if let Some(n) = app.top_n.filter(|&n| n < app.fields.len()) {
+        spans.push(Span::styled(format!("  [top {} of {}]", n, app.fields.len()), Style::default().fg(theme.focus)));
+    }
+    if let Some(limit) = app.sample_limit {
+        spans.push(Span::styled(format!("  [{}/{} samples]", app.samples_seen, limit), Style::default().fg(theme.warning)));
+    }
+    if app.scroll_offset > 0 {
+        spans.push(Span::styled(format!("  ◀ {} samples back", app.scroll_offset), Style::default().fg(theme.focus)));
+    }
+    if let Some(pid) = app.watch_pid {
+        spans.push(Span::styled(format!("  [watching PID {}]", pid), Style::default().fg(theme.focus)));
+    }
+    let bells: u32 = app.percentile_alarms.iter().map(|alarm| alarm.bell_count).sum();
+    if bells > 0 {
+        spans.push(Span::styled(format!("  [alarms: {}]", bells), Style::default().fg(theme.alert)));
+    }
+    if let Some(duration) = app.duration {
+        let remaining = duration.saturating_sub(app.started.elapsed());
+        spans.push(Span::styled(format!("  quits in {}", format_duration(remaining)), Style::default().fg(theme.warning)));
+    }
+    // Wide terminals have room to spell out which GPUs are being monitored
+    if area.width >= 100 {
+        let ids: Vec<String> = app.gpus.iter().map(|id| id.to_string()).collect();
+        spans.push(Span::raw(format!("  GPUs: {}", ids.join(","))));
+    }
+    let header = Paragraph::new(Line::from(spans)).style(Style::default().fg(theme.text));
+    f.render_widget(header, area);
+
+    if app.is_paused {
+        let badge = Paragraph::new(Span::styled("[PAUSED]", Style::default().fg(theme.background_or(Color::Black)).bg(theme.warning).add_modifier(Modifier::BOLD)))
+            .alignment(Alignment::Right);
+        f.render_widget(badge, area);
+    }
+}
+
+fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
+    let mut lines: Vec<Line> = app.footer_stderr().iter()
+        .map(|line| Line::from(Span::styled(*line, Style::default().fg(app.theme.warning))))
+        .collect();
+    match app.annotation {
+        Some(ref text) => lines.push(Line::from(vec![
+            Span::styled("annotate: ", Style::default().fg(app.theme.focus).add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{}█", text), Style::default().fg(app.theme.value)),
+            Span::styled("  (Enter to save, Esc to cancel)", Style::default().fg(app.theme.dim)),
+        ])),
+        None => lines.push(Line::from(Span::styled(app.last_raw_line.as_str(), Style::default().fg(app.theme.dim).add_modifier(Modifier::DIM)))),
+    }
+    f.render_widget(Paragraph::new(lines), area);
+    let warning = Style::default().fg(app.theme.warning);
+    let mut counters = Vec::new();
+    if let Some(average) = app.average_draw_time() {
+        let ms = average.as_secs_f64() * 1000.0;
+        // Drawing for more than half of each interval leaves little time for sampling
+        if ms > app.interval_ms as f64 / 2.0 {
+            counters.push(Span::styled(format!("[draw: {:.1}ms avg, over half the interval; lower --max-fps]", ms), Style::default().fg(app.theme.alert)));
+        } else {
+            counters.push(Span::styled(format!("[draw: {:.1}ms avg]", ms), warning));
+        }
+    }
+    if app.show_parse_errors || app.parse_errors > 0 {
+        counters.push(Span::styled(format!("[parse errors: {}]", app.parse_errors), warning));
+    }
+    if app.min_sample_gap.is_some() {
+        counters.push(Span::styled(format!("[dropped: {}]", app.dropped), warning));
+    }
+    if !counters.is_empty() {
+        let spans: Vec<Span> = counters.into_iter().enumerate().flat_map(|(i, counter)| [(i > 0).then(|| Span::raw(" ")), Some(counter)]).flatten().collect();
+        let counter = Paragraph::new(Line::from(spans))
+            .alignment(Alignment::Right);
+        f.render_widget(counter, Rect { y: area.bottom() - 1, height: 1, ..area });
+    }
+}
+
+fn draw_error(f: &mut Frame, message: &str, app: &App) {
+    let mut lines = vec![
+        Line::from(Span::styled(message, Style::default().fg(app.theme.alert).add_modifier(Modifier::BOLD))),
+        Line::from(""),
+    ];
+    if !app.stderr_lines.is_empty() {
+        lines.push(Line::from(Span::styled("stderr:", Style::default().add_modifier(Modifier::BOLD))));
+        lines.extend(app.stderr_lines.iter().map(|l| Line::from(l.as_str())));
+        lines.push(Line::from(""));
+    }
+    lines.push(Line::from(Span::styled("Press q to quit", Style::default().fg(app.theme.text))));
+    let error = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(app.theme.border)).title("dcgmi stopped"))
+        .style(Style::default().fg(app.theme.value).bg(app.theme.background))
+        .wrap(Wrap { trim: false });
+    f.render_widget(error, f.size());
+}
+
+/// Renders a frame and returns where each metric row was drawn (empty outside the overview)
+fn draw(f: &mut Frame, app: &App) -> Vec<(Rect, usize)> {
+    if let AppState::Error(ref message) = app.state {
+        draw_error(f, message, app);
+        return Vec::new();
+    }
+    if app.watchface {
+        draw_watchface(f, app);
+        return Vec::new();
+    }
+    f.render_widget(Block::default().style(Style::default().bg(app.theme.background)), f.size());
+    let outer = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1 + app.footer_stderr().len() as u16)])
+        .split(f.size());
+    draw_header(f, outer[0], app);
+    draw_footer(f, outer[2], app);
+    let mut area = outer[1];
+    // Only show the GPU tab strip when there is more than one GPU to pick from
+    if app.gpus.len() > 1 && app.aggregate.is_none() && app.diff_gpus.is_none() && app.dual_gpus.is_none() {
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(area);
+        // MIG instances sort straight after their parent GPU, so they read as its sub-tabs
+        let titles: Vec<String> = app.gpus.iter().map(|entity| match (entity.instance, app.gpu_names.get(&entity.gpu)) {
+            (Some(_), _) => format!("└ MIG {}", entity),
+            (None, Some(name)) => format!("GPU {} {}", entity, name),
+            (None, None) => format!("GPU {}", entity),
+        }).collect();
+        let tabs = Tabs::new(titles)
+            .select(app.active_gpu)
+            .style(Style::default().fg(app.theme.text))
+            .highlight_style(Style::default().fg(app.theme.bar).add_modifier(Modifier::BOLD));
+        f.render_widget(tabs, outer[0]);
+        area = outer[1];
+    }
+
+    let combined;
+    let history = match (app.aggregate, app.diff_gpus) {
+        (Some(aggregate), _) => {
+            combined = aggregate_history(app.displayed_history(), aggregate, app.fields.len());
+            &combined
+        }
+        (None, Some((a, b))) => {
+            combined = diff_history(app.displayed_history(), a, b, app.fields.len());
+            &combined
+        }
+        (None, None) => &app.displayed_history()[&app.dual_focus().unwrap_or(app.gpus[app.active_gpu])],
+    };
+
+    let mut metric_rows = Vec::new();
+    let samples = history.first().map_or(0, |buf| buf.len());
+    match app.view {
+        _ if app.collecting_for(samples).is_some() => draw_collecting(f, area, samples, app),
+        AppView::Detail(metric) => {
+            draw_detail(f, area, metric, history, app);
+        }
+        AppView::Topology => draw_topology(f, area, app),
+        AppView::Heatmap => draw_heatmap(f, area, history, app),
+        AppView::Correlation => draw_correlation(f, area, history, app),
+        AppView::Overview if app.dual_gpus.is_some() => metric_rows = draw_dual_compare(f, area, app),
+        AppView::Overview => {
+            let area = match app.shown_processes() {
+                Some(processes) => {
+                    let split = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(0), Constraint::Length(PROCESS_PANEL_ROWS)])
+                        .split(area);
+                    draw_processes(f, split[1], processes, app);
+                    split[0]
+                }
+                None => area,
+            };
+            // Each metric row, preceded by a divider wherever --metric-group starts a new group
+            let mut rows: Vec<(Option<MetricGroup>, usize)> = Vec::new();
+            for metric in app.row_order() {
+                let group = MetricGroup::of(app.fields[metric].0);
+                if app.metric_groups && rows.last().is_none_or(|(_, prev)| MetricGroup::of(app.fields[*prev].0) != group) {
+                    rows.push((Some(group), metric));
+                }
+                rows.push((None, metric));
+            }
+            let mut constraints: Vec<Constraint> = rows.iter().map(|(divider, _)| Constraint::Length(if divider.is_some() { 1 } else { 3 })).collect();
+            if app.group_id.is_some() {
+                constraints.push(Constraint::Length(3));
+            }
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .horizontal_margin(1)
+                .constraints(constraints)
+                .split(area);
+            for (row, &(divider, metric)) in rows.iter().enumerate() {
+                match divider {
+                    Some(group) => draw_group_divider(f, layout[row], group, app),
+                    None => {
+                        draw_metric(f, layout[row], metric, history, app);
+                        metric_rows.push((layout[row], metric));
+                    }
+                }
+            }
+            if let Some(group) = app.group_id {
+                draw_group_summary(f, layout[rows.len()], group, app);
+            }
+        }
+    }
+
+    if let Some(ref history) = app.fps_history {
+        draw_fps_chart(f, history, app);
+    }
+    if app.mode == AppMode::HelpOverlay {
+        draw_help(f, app);
+    }
+    metric_rows
+}
+
+/// --dual-gpu-compare: every metric of GPU A on the left and GPU B on the right, each
+/// scrolled on its own, with A - B between them for the rows of the focused side
+fn draw_dual_compare(f: &mut Frame, area: Rect, app: &App) -> Vec<(Rect, usize)> {
+    let Some((a, b)) = app.dual_gpus else {
+        return Vec::new();
+    };
+    let side_width = area.width.saturating_sub(DUAL_DELTA_WIDTH) / 2;
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(side_width), Constraint::Length(DUAL_DELTA_WIDTH), Constraint::Min(0)])
+        .split(area);
+    let order = app.row_order();
+    let mut metric_rows = Vec::new();
+    let mut shown_rows = [Vec::new(), Vec::new()];
+    for (side, (entity, column)) in [(a, columns[0]), (b, columns[2])].into_iter().enumerate() {
+        let focused = app.dual_side == side;
+        let title = match app.gpu_names.get(&entity.gpu) {
+            Some(name) => format!(" GPU {} {} ", entity, name),
+            None => format!(" GPU {} ", entity),
+        };
+        let title_style = if focused { Style::default().fg(app.theme.focus).add_modifier(Modifier::BOLD) } else { Style::default().fg(app.theme.value) };
+        let block = Block::default().borders(Borders::TOP).border_style(Style::default().fg(app.theme.border)).title(Span::styled(title, title_style));
+        let inner = block.inner(column);
+        f.render_widget(block, column);
+        let Some(history) = app.displayed_history().get(&entity) else {
+            continue;
+        };
+        let visible = (inner.height / 3) as usize;
+        let scroll = app.dual_scroll[side].min(order.len().saturating_sub(visible));
+        for (row, &metric) in order.iter().skip(scroll).take(visible).enumerate() {
+            let rect = Rect { y: inner.y + row as u16 * 3, height: 3, ..inner };
+            draw_metric(f, rect, metric, history, app);
+            metric_rows.push((rect, metric));
+            shown_rows[side].push((rect.y, metric));
+        }
+    }
+    let histories = app.displayed_history();
+    let (Some(a), Some(b)) = (histories.get(&a), histories.get(&b)) else {
+        return metric_rows;
+    };
+    let delta = columns[1];
+    f.render_widget(Block::default().borders(Borders::TOP).border_style(Style::default().fg(app.theme.border)).title(Span::styled(" A - B ", Style::default().fg(app.theme.value))), delta);
+    for &(y, metric) in &shown_rows[app.dual_side] {
+        let kind = metric_kind(app.fields[metric].0);
+        let (text, color) = match (a[metric].back(), b[metric].back()) {
+            (Some(x), Some(y)) if x > y => (format_signed(kind, x - y), app.theme.bar),
+            (Some(x), Some(y)) if x < y => (format_signed(kind, x - y), app.theme.alert),
+            (Some(_), Some(_)) => (format_signed(kind, 0.0), app.theme.dim),
+            _ => ("-".to_string(), app.theme.dim),
+        };
+        let lines = vec![
+            Line::from(Span::styled(app.fields[metric].1.clone(), Style::default().fg(app.theme.text))),
+            Line::from(Span::styled(text, Style::default().fg(color).add_modifier(Modifier::BOLD))),
+        ];
+        f.render_widget(Paragraph::new(lines).alignment(Alignment::Center), Rect { y, height: 2.min(delta.bottom().saturating_sub(y)), ..delta });
+    }
+    metric_rows
+}
+
+/// --silent-start placeholder shown while the history buffers fill
+fn draw_collecting(f: &mut Frame, area: Rect, samples: usize, app: &App) {
+    let remaining = app.collecting_for(samples).unwrap_or_default();
+    let lines = vec![
+        Line::from(Span::styled("Collecting data...", Style::default().fg(app.theme.value).add_modifier(Modifier::BOLD))),
+        Line::from(Span::styled(format!("{} / {} samples, charts in {}s", samples, app.history_len, remaining.as_secs_f64().ceil() as u64), Style::default().fg(app.theme.dim))),
+    ];
+    let top = area.y + area.height.saturating_sub(lines.len() as u16) / 2;
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center), Rect { y: top, height: area.bottom().saturating_sub(top), ..area });
+}
+
+/// --fps-chart: the latest rate above a sparkline of the most recent ones, under the header
+fn draw_fps_chart(f: &mut Frame, history: &VecDeque<f64>, app: &App) {
+    let screen = f.size();
+    if screen.width < FPS_CHART_WIDTH || screen.height < FPS_CHART_HEIGHT + 2 {
+        return;
+    }
+    let area = Rect::new(screen.right() - FPS_CHART_WIDTH, screen.top() + 1, FPS_CHART_WIDTH, FPS_CHART_HEIGHT + 1);
+    f.render_widget(Clear, area);
+    let current = history.back().map_or("fps: --".to_string(), |fps| format!("fps: {:.1}", fps));
+    f.render_widget(Paragraph::new(current).alignment(Alignment::Right).style(Style::default().fg(app.theme.value).bg(app.theme.background)), Rect { height: 1, ..area });
+    // Tenths keep the sparkline's integer heights from flattening low rates
+    let recent: Vec<u64> = history.iter().skip(history.len().saturating_sub(FPS_CHART_WIDTH as usize)).map(|fps| (fps * 10.0) as u64).collect();
+    let sparkline = Sparkline::default().data(&recent).style(Style::default().fg(app.theme.focus).bg(app.theme.background));
+    f.render_widget(sparkline, Rect { y: area.y + 1, height: FPS_CHART_HEIGHT, ..area });
+}
+
+/// PID, name and memory of each process on the active GPU, scrolled with PgUp / PgDn
+fn draw_processes(f: &mut Frame, area: Rect, processes: &[GpuProcess], app: &App) {
+    let gpu = app.gpus[app.active_gpu].gpu;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Processes on GPU {} ({}) ", gpu, processes.len()))
+        .border_style(Style::default().fg(app.theme.border));
+    let mut lines = vec![Line::from(Span::styled(format!("{:>8}  {:<32} {:>10}", "PID", "NAME", "MEMORY"), Style::default().fg(app.theme.value).add_modifier(Modifier::BOLD)))];
+    if processes.is_empty() {
+        lines.push(Line::from(Span::styled("  no compute processes", Style::default().fg(app.theme.dim))));
+    }
+    let visible = area.height.saturating_sub(3) as usize;
+    let scroll = app.process_scroll.min(processes.len().saturating_sub(visible));
+    for process in processes.iter().skip(scroll).take(visible) {
+        let memory = format_bytes_with_unit(process.memory_mb * 1024.0 * 1024.0, false);
+        lines.push(Line::from(Span::styled(format!("{:>8}  {:<32} {:>10}", process.pid, process.name, memory), Style::default().fg(app.theme.text))));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// `GPU0 | SM:87% | PCIe↑:8.3GB/s ↓:2.1GB/s | TEMP:71°C` for every GPU; a shown TX field
+/// absorbs its RX partner so each link reads as one cell
+fn draw_watchface(f: &mut Frame, app: &App) {
+    let theme = app.theme;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(app.gpus.iter().map(|_| Constraint::Length(1)).collect::<Vec<_>>())
+        .split(f.size());
+    let shown = app.display_order();
+    let partner = |metric: usize| {
+        let id = app.fields[metric].0;
+        shown.iter().copied().find(|&m| matches!(id, 1009 | 1011) && app.fields[m].0 == id + 1)
+    };
+    for (row, entity) in rows.iter().zip(&app.gpus) {
+        let Some(buffers) = app.displayed_history().get(entity) else {
+            continue;
+        };
+        let value = |metric: usize| {
+            let text = buffers[metric].back().map_or("-".to_string(), |&v| format_compact_value(metric_kind(app.fields[metric].0), v));
+            Span::styled(text, Style::default().fg(app.threshold_color(metric, &buffers[metric])))
+        };
+        let mut spans = vec![Span::styled(format!("GPU{}", entity), Style::default().fg(theme.value).add_modifier(Modifier::BOLD))];
+        for &metric in &shown {
+            if shown.iter().any(|&tx| partner(tx) == Some(metric)) {
+                continue;
+            }
+            let (id, ref name) = app.fields[metric];
+            let combined = app.columns.get(metric).is_some_and(|columns| columns.len() > 1);
+            let aliased = app.aliases.iter().any(|(_, alias)| alias == name);
+            spans.push(Span::styled(" | ", Style::default().fg(theme.dim)));
+            spans.push(Span::styled(format!("{}:", watchface_label(id, name, combined, aliased)), Style::default().fg(theme.text)));
+            spans.push(value(metric));
+            if let Some(rx) = partner(metric) {
+                spans.push(Span::styled(" ↓:", Style::default().fg(theme.text)));
+                spans.push(value(rx));
+            }
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)).style(Style::default().bg(theme.background)), *row);
+    }
+}
+
+/// Bold `── Memory ─────` rule above a --metric-group section
+fn draw_group_divider(f: &mut Frame, area: Rect, group: MetricGroup, app: &App) {
+    let label = format!("── {} ", group.label());
+    let rule = "─".repeat((area.width as usize).saturating_sub(label.chars().count()));
+    let line = Line::from(vec![Span::raw(label), Span::styled(rule, Style::default().fg(app.theme.dim))]);
+    f.render_widget(Paragraph::new(line).style(Style::default().fg(app.theme.value).add_modifier(Modifier::BOLD)), area);
+}
+
+/// One line with the mean of every metric's latest value across the group's GPUs
+fn draw_group_summary(f: &mut Frame, area: Rect, group: u32, app: &App) {
+    let latest: Vec<&Vec<MetricBuffer>> = aggregated_values(app.displayed_history()).collect();
+    let mut spans = Vec::new();
+    for (metric, (id, name)) in app.fields.iter().enumerate() {
+        let values: Vec<f64> = latest.iter().filter_map(|buffers| buffers[metric].back().copied()).collect();
+        spans.push(Span::styled(format!("{} ", name), Style::default().fg(app.theme.value)));
+        spans.push(Span::styled(format!("{}  ", format_metric_value(metric_kind(*id), Aggregate::Mean.apply(&values))), Style::default().fg(app.theme.text)));
+    }
+    let summary = Paragraph::new(Line::from(spans)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(app.theme.border))
+            .title(Span::styled(format!("Group {} mean of {} GPUs", group, latest.len()), Style::default().fg(app.theme.value).add_modifier(Modifier::BOLD))),
+    );
+    f.render_widget(summary, area);
+}
+
+/// Returns a rectangle of the given percentage size centred inside `r`
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(r);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+fn draw_help(f: &mut Frame, app: &App) {
+    let key_width = KEYBINDINGS.iter().chain(CLI_HINTS).map(|(key, _)| key.chars().count()).max().unwrap_or(0);
+    let entry = |(key, action): &(&str, &'static str)| {
+        Line::from(vec![
+            Span::styled(format!("{:<width$}  ", key, width = key_width), Style::default().fg(app.theme.bar).add_modifier(Modifier::BOLD)),
+            Span::raw(*action),
+        ])
+    };
+    let mut lines: Vec<Line> = KEYBINDINGS.iter().map(entry).collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Options", Style::default().add_modifier(Modifier::BOLD))));
+    lines.extend(CLI_HINTS.iter().map(entry));
+    if !app.aliases.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Aliases", Style::default().add_modifier(Modifier::BOLD))));
+        let width = app.aliases.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+        lines.extend(app.aliases.iter().map(|(name, alias)| Line::from(vec![
+            Span::styled(format!("{:<width$}  ", name, width = width), Style::default().fg(app.theme.bar).add_modifier(Modifier::BOLD)),
+            Span::raw(alias.clone()),
+        ])));
+    }
+    let area = centered_rect(60, 80, f.size());
+    // No background and no Clear: the charts beneath stay visible around the text, dimmed
+    // by the paragraph's style while the text itself keeps the value colour
+    let help = Paragraph::new(Text::from(lines).style(Style::default().fg(app.theme.value)))
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(app.theme.border)).title(Span::styled("Keybindings", Style::default().fg(app.theme.value))))
+        .style(Style::default().fg(app.theme.dim))
+        .wrap(Wrap { trim: false });
+    f.render_widget(help, area);
+}
+
+/// Clicking a metric row opens its detail view; the wheel scrolls the charts through time
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) if app.mode == AppMode::Normal && app.view == AppView::Overview => {
+            let clicked = app.last_layout.iter().find(|(rect, _)| (rect.left()..rect.right()).contains(&mouse.column) && (rect.top()..rect.bottom()).contains(&mouse.row));
+            if let Some(&(_, metric)) = clicked {
+                app.cursor = metric;
+                app.view = AppView::Detail(metric);
+            }
+        }
+        MouseEventKind::ScrollUp => app.scroll(true),
+        MouseEventKind::ScrollDown => app.scroll(false),
+        _ => {}
+    }
+}
+
+/// Applies a keypress to the app state. Returns true when the app should quit.
+fn handle_key(app: &mut App, key: KeyEvent) -> bool {
+    if app.mode == AppMode::HelpOverlay {
+        match key.code {
+            KeyCode::Char('q') => return true,
+            KeyCode::Char('?') | KeyCode::Esc => app.mode = AppMode::Normal,
+            _ => {}
+        }
+        return false;
+    }
+
+    if let Some(ref mut text) = app.annotation {
+        match key.code {
+            KeyCode::Enter => {
+                let text = app.annotation.take().unwrap_or_default();
+                if !text.trim().is_empty() {
+                    app.pending_annotations.push(text.trim().to_string());
+                }
+            }
+            KeyCode::Esc => app.annotation = None,
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) => text.push(c),
+            _ => {}
+        }
+        return false;
+    }
+
+    if matches!(app.view, AppView::Detail(_) | AppView::Topology | AppView::Heatmap | AppView::Correlation) && matches!(key.code, KeyCode::Esc | KeyCode::Char('q')) {
+        app.view = AppView::Overview;
+        return false;
+    }
+
+    if app.compare.is_some() && key.code == KeyCode::Esc {
+        app.compare = None;
+        return false;
+    }
+    if let (Some(Compare::Picking(charted)), KeyCode::Enter) = (app.compare, key.code) {
+        if app.cursor != charted {
+            app.compare = Some(Compare::Showing(charted, app.cursor));
+            app.cursor = charted;
+        }
+        return false;
+    }
+
+    match key.code {
+        KeyCode::Char('q') => return true,
+        KeyCode::Char('?') => app.mode = AppMode::HelpOverlay,
+        KeyCode::Char('c') if app.view == AppView::Overview => app.compare = Some(Compare::Picking(app.cursor)),
+        KeyCode::Char('a') => app.annotation = Some(String::new()),
+        KeyCode::Up if app.dual_gpus.is_some() && app.view == AppView::Overview => app.scroll_dual(false),
+        KeyCode::Down if app.dual_gpus.is_some() && app.view == AppView::Overview => app.scroll_dual(true),
+        KeyCode::Tab | KeyCode::BackTab if app.dual_gpus.is_some() => app.dual_side ^= 1,
+        KeyCode::Up => app.move_cursor(false),
+        KeyCode::Down => app.move_cursor(true),
+        KeyCode::Enter => app.view = AppView::Detail(app.cursor),
+        KeyCode::Char('t') if app.view == AppView::Topology => app.view = AppView::Overview,
+        KeyCode::Char('h') if app.view == AppView::Heatmap => app.view = AppView::Overview,
+        KeyCode::Char('h') => app.view = AppView::Heatmap,
+        KeyCode::Char('C') if app.view == AppView::Correlation => app.view = AppView::Overview,
+        KeyCode::Char('C') => app.view = AppView::Correlation,
+        KeyCode::Char('r') => match app.processes {
+            Some(_) => app.show_processes = !app.show_processes,
+            None => app.flash("Start with --show-procs to list GPU processes".to_string(), true),
+        },
+        KeyCode::PageUp => app.process_scroll = app.process_scroll.saturating_sub(1),
+        KeyCode::PageDown if app.shown_processes().is_some_and(|p| app.process_scroll + 1 < p.len()) => app.process_scroll += 1,
+        KeyCode::Char('t') => match app.topology {
+            Some(_) => app.view = AppView::Topology,
+            None => app.flash("Start with --topo to query the topology".to_string(), true),
+        },
+        KeyCode::Char('v') => app.display_mode = app.display_mode.toggle(),
+        KeyCode::Char('p') => app.toggle_pause(),
+        KeyCode::Char('s') => app.scale_mode = app.scale_mode.next(),
+        KeyCode::Char('+') => app.zoom(true),
+        KeyCode::Char('-') => app.zoom(false),
+        KeyCode::Char('o') => {
+            app.sort_mode = app.sort_mode.next();
+            app.flash(format!("Sort: {}", app.sort_mode.label()), false);
+        }
+        KeyCode::Char('S') => match app.save_snapshot() {
+            Ok(_) => app.flash("Saved!".to_string(), false),
+            Err(e) => app.flash(format!("Snapshot failed: {}", e), true),
+        },
+        KeyCode::Char('P') => app.export_requested = true,
+        KeyCode::Tab => app.active_gpu = (app.active_gpu + 1) % app.gpus.len(),
+        KeyCode::BackTab => app.active_gpu = (app.active_gpu + app.gpus.len() - 1) % app.gpus.len(),
+        KeyCode::Char(' ') => {
+            if let Some(ref mut replay) = app.replay {
+                replay.paused = !replay.paused;
+            }
+        }
+        KeyCode::Right if app.replay.is_some() => app.step_replay(true),
+        KeyCode::Left if app.replay.is_some() => app.step_replay(false),
+        KeyCode::Right => app.scroll(false),
+        KeyCode::Left => app.scroll(true),
+        _ => {}
+    }
+    false
+}
+
+/// Title block and bar colour for a metric, reflecting focus and alert state
+fn metric_block(metric: usize, buf: &MetricBuffer, app: &App) -> (Block<'static>, Color) {
+    let (title_style, bar_color) = if app.row_alerting(metric) {
+        (Style::default().fg(app.theme.alert).add_modifier(Modifier::BOLD), app.theme.alert)
+    } else if app.deviates_from_baseline(metric, buf) {
+        (Style::default().fg(app.theme.warning).add_modifier(Modifier::BOLD), app.threshold_color(metric, buf))
+    } else {
+        (Style::default().fg(app.theme.value), app.threshold_color(metric, buf))
+    };
+    let picking = app.compare == Some(Compare::Picking(metric));
+    let border_style = if picking {
+        Style::default().fg(app.theme.warning)
+    } else if app.cursor == metric && app.view == AppView::Overview {
+        Style::default().fg(app.theme.focus)
+    } else if app.is_derived(metric) {
+        Style::default().fg(app.theme.derived)
+    } else {
+        Style::default().fg(app.theme.border)
+    };
+    let delta = if app.delta.get(metric).copied().unwrap_or(false) { " Δ" } else { "" };
+    let window = Duration::from_millis(app.zoom_window() as u64 * app.interval_ms);
+    let per_lane = if app.per_lane(metric) { "/lane" } else { "" };
+    let mut title = vec![Span::styled(format!("{}{}{}", app.fields[metric].1, per_lane, delta), title_style)];
+    if let Some(rx) = app.stacked_rx(metric) {
+        title.push(Span::styled(format!("+{} ", app.fields[rx].1), title_style));
+        title.push(Span::styled("█", Style::default().fg(app.theme.bar)));
+        title.push(Span::styled(" TX ", title_style));
+        title.push(Span::styled("█", Style::default().fg(app.theme.focus)));
+        title.push(Span::styled(" RX", title_style));
+    }
+    if picking {
+        title.push(Span::styled(" vs ? (pick, Enter)", Style::default().fg(app.theme.warning)));
+    } else if let Some(overlaid) = app.compare_overlay(metric) {
+        title.push(Span::styled(" vs ", title_style));
+        title.push(Span::styled(app.fields[overlaid].1.clone(), Style::default().fg(app.theme.focus).add_modifier(Modifier::BOLD)));
+    }
+    title.push(Span::styled(format!(" {} [{}]", app.scale_mode.suffix(), format_window(window)), title_style));
+    if let Some(range) = app.temp_range(metric, buf) {
+        title.push(Span::styled(format!(" [{}]", range.name), Style::default().fg(range.color)));
+    }
+    if app.smooth > 1 {
+        title.push(Span::styled(format!(" [smoothed ×{}]", app.smooth), title_style));
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style)
+        .title(Line::from(title));
+    (block, bar_color)
+}
+
+/// `buf[start..end]`, each value replaced by the mean of it and up to `window - 1` samples
+/// before it (including ones left of `start`, so the first bars are not cut short)
+fn smoothed(buf: &MetricBuffer, start: usize, end: usize, window: usize) -> Vec<f64> {
+    (start..end)
+        .map(|j| {
+            let from = (j + 1).saturating_sub(window);
+            buf.range(from..=j).sum::<f64>() / (j + 1 - from) as f64
+        })
+        .collect()
+}
+
+/// Renders `metric`'s buffer as bars or a sparkline depending on the display mode. In
+/// bar view a compared metric is drawn in the gaps between the bars, each series scaled
+/// to its own peak.
+fn draw_chart(f: &mut Frame, area: Rect, block: Block, history: &[MetricBuffer], bar_color: Color, metric: usize, app: &App) {
+    let buf = &history[metric];
+    if app.diff_gpus.is_some() {
+        draw_signed_chart(f, area, block, buf, app);
+        return;
+    }
+    if let Some(rx) = app.stacked_rx(metric) {
+        draw_stacked_chart(f, area, block, buf, &history[rx], app);
+        return;
+    }
+    let inner = block.inner(area);
+    // Bars are one column wide plus a one-column gap; sparkline points are one column each
+    let fits = match app.display_mode {
+        DisplayMode::Bar => (inner.width as usize).div_ceil(2),
+        DisplayMode::Sparkline => inner.width as usize,
+    };
+    let (start, end) = app.chart_range(buf.len(), fits);
+    let height = |value: f64| (app.scale_mode.apply(value) * 100.0) as u64;
+    let max_value = app.max_value(metric).map(|max| app.per_lane_value(metric, max));
+    let ceiling = max_value.map(height);
+    let values = smoothed(buf, start, end, app.smooth);
+    let mut scaled: Vec<u64> = values.iter().map(|val| height(*val).min(ceiling.unwrap_or(u64::MAX))).collect();
+    let clipped: Vec<bool> = values.iter().map(|val| max_value.is_some_and(|max| *val > max)).collect();
+    // BarChart gives its labels the bottom row whenever there is more than one
+    let rows = match app.display_mode {
+        DisplayMode::Bar => inner.height - u16::from(inner.height >= 2),
+        DisplayMode::Sparkline => inner.height,
+    };
+    let mut raised = vec![false; scaled.len()];
+    if let Some(pct) = app.min_bar_height {
+        let peak = ceiling.unwrap_or_else(|| scaled.iter().copied().max().unwrap_or(0)) as f64;
+        // Never less than one eighth of a row, the smallest step a bar can show
+        let floor = (peak * pct / 100.0).max(peak / (rows.max(1) as f64 * 8.0)).ceil() as u64;
+        for (value, raised) in scaled.iter_mut().zip(raised.iter_mut()) {
+            if *value > 0 && *value < floor {
+                *value = floor;
+                *raised = true;
+            }
+        }
+    }
+    match app.display_mode {
+        DisplayMode::Bar => {
+            let labels: Vec<String> = (start..end).map(|j| j.to_string()).collect();
+            let bar_data: Vec<(&str, u64)> = labels.iter().map(|l| l.as_str()).zip(scaled.iter().copied()).collect();
+            let mut barchart = BarChart::default()
+                .block(block)
+                .data(&bar_data)
+                .bar_width(1)
+                .bar_style(Style::default().fg(bar_color))
+                .value_style(Style::default().fg(app.theme.value).add_modifier(Modifier::BOLD))
+                .bar_set(CUSTOM_SET);
+            if let Some(ceiling) = ceiling {
+                barchart = barchart.max(ceiling);
+            }
+            f.render_widget(barchart, area);
+            recolor_columns(f, Rect { height: rows, ..inner }, &clipped, 2, app.theme.warning);
+            if let Some(colour) = app.min_bar_colour {
+                recolor_columns(f, Rect { height: rows, ..inner }, &raised, 2, colour);
+            }
+            if let Some(overlay) = app.compare_overlay(metric).map(|m| &history[m]) {
+                let values: Vec<f64> = overlay.range(start.min(overlay.len())..end.min(overlay.len())).map(|val| app.scale_mode.apply(*val)).collect();
+                let peak = values.iter().copied().fold(0.0, f64::max);
+                if peak > 0.0 {
+                    let normalised: Vec<f64> = values.iter().map(|v| v / peak).collect();
+                    draw_gap_bars(f, inner, &normalised, app.theme.focus);
+                }
+            }
+            if let Some(base) = app.baseline_p50(metric) {
+                draw_level_line(f, inner, &scaled, height(app.per_lane_value(metric, base)), "┄", app.theme.warning);
+            }
+            if let Some(ema) = app.ema_of(metric) {
+                draw_level_line(f, inner, &scaled, height(app.per_lane_value(metric, ema)), "─", app.theme.text);
+            }
+        }
+        DisplayMode::Sparkline => {
+            let mut sparkline = Sparkline::default()
+                .block(block)
+                .data(&scaled)
+                .style(Style::default().fg(bar_color));
+            if let Some(ceiling) = ceiling {
+                sparkline = sparkline.max(ceiling);
+            }
+            f.render_widget(sparkline, area);
+            recolor_columns(f, inner, &clipped, 1, app.theme.warning);
+            if let Some(colour) = app.min_bar_colour {
+                recolor_columns(f, inner, &raised, 1, colour);
+            }
+        }
+    }
+    if app.graph_style == GraphStyle::Down {
+        hang_from_top(f, Rect { height: rows, ..inner });
+    }
+}
+
+/// Mirrors a drawn chart top to bottom for --graph-style down. Only three top-anchored
+/// block characters exist, so each partial cell is rounded to an eighth, a half or full.
+fn hang_from_top(f: &mut Frame, area: Rect) {
+    let buffer = f.buffer_mut();
+    for x in area.left()..area.right() {
+        for row in 0..area.height / 2 {
+            let (top, bottom) = (area.top() + row, area.bottom() - 1 - row);
+            let upper = buffer.get(x, top).clone();
+            let lower = std::mem::replace(buffer.get_mut(x, bottom), upper);
+            *buffer.get_mut(x, top) = lower;
+        }
+        for y in area.top()..area.bottom() {
+            let cell = buffer.get_mut(x, y);
+            let flipped = match cell.symbol() {
+                "▁" | "▂" => "▔",
+                "▃" | "▄" | "▅" => "▀",
+                "▆" | "▇" => "█",
+                _ => continue,
+            };
+            cell.set_symbol(flipped);
+        }
+    }
+}
+
+/// Recolours the drawn cells of every flagged chart column, `stride` columns apart
+fn recolor_columns(f: &mut Frame, inner: Rect, flagged: &[bool], stride: u16, color: Color) {
+    let buffer = f.buffer_mut();
+    for (i, _) in flagged.iter().enumerate().filter(|(_, flagged)| **flagged) {
+        let x = inner.left() + stride * i as u16;
+        if x >= inner.right() {
+            break;
+        }
+        for y in inner.top()..inner.bottom() {
+            let cell = buffer.get_mut(x, y);
+            if cell.symbol() != " " {
+                cell.set_fg(color);
+            }
+        }
+    }
+}
+
+/// --stacked-bar: each column is TX + RX tall, TX at the bottom in the bar colour and RX on
+/// top in the focus colour, split in proportion to the two values
+fn draw_stacked_chart(f: &mut Frame, area: Rect, block: Block, tx: &MetricBuffer, rx: &MetricBuffer, app: &App) {
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let step = match app.display_mode {
+        DisplayMode::Bar => 2,
+        DisplayMode::Sparkline => 1,
+    };
+    // Both buffers grow together, but a fresh MIG entity can briefly lag by a sample
+    let len = tx.len().min(rx.len());
+    let (start, end) = app.chart_range(len, (inner.width as usize).div_ceil(step));
+    let tx = tx.range(tx.len() - len..).skip(start).take(end - start);
+    let rx = rx.range(rx.len() - len..).skip(start).take(end - start);
+    let columns: Vec<(f64, f64)> = tx.zip(rx).map(|(tx, rx)| (*tx, *rx)).collect();
+    let peak = columns.iter().fold(0.0, |peak: f64, (tx, rx)| peak.max(app.scale_mode.apply(tx + rx)));
+    if peak <= 0.0 || inner.height == 0 {
+        return;
+    }
+    let partial = [
+        CUSTOM_SET.empty, CUSTOM_SET.one_eighth, CUSTOM_SET.one_quarter, CUSTOM_SET.three_eighths,
+        CUSTOM_SET.half, CUSTOM_SET.five_eighths, CUSTOM_SET.three_quarters, CUSTOM_SET.seven_eighths,
+    ];
+    let rows = inner.height as usize;
+    let buffer = f.buffer_mut();
+    for (i, &(tx, rx)) in columns.iter().enumerate() {
+        let x = inner.left() + (step * i) as u16;
+        if x >= inner.right() {
+            break;
+        }
+        let total = tx + rx;
+        let total_eighths = (app.scale_mode.apply(total) / peak * rows as f64 * 8.0).round() as usize;
+        let tx_eighths = if total > 0.0 { (total_eighths as f64 * tx / total).round() as usize } else { 0 };
+        for row in 0..rows {
+            let (bottom, top) = (row * 8, row * 8 + 8);
+            if total_eighths <= bottom {
+                break;
+            }
+            let cell = buffer.get_mut(x, inner.bottom() - 1 - row as u16);
+            if tx_eighths >= top {
+                cell.set_symbol(CUSTOM_SET.full).set_fg(app.theme.bar);
+            } else if tx_eighths > bottom {
+                // The lower part of the cell is TX; RX fills the rest as the background
+                cell.set_symbol(partial[tx_eighths - bottom]).set_fg(app.theme.bar).set_bg(app.theme.focus);
+            } else if total_eighths >= top {
+                cell.set_symbol(CUSTOM_SET.full).set_fg(app.theme.focus);
+            } else {
+                cell.set_symbol(partial[total_eighths - bottom]).set_fg(app.theme.focus);
+            }
+        }
+    }
+}
+
+/// Bars for a signed series: green growing up from the middle for positive values, red
+/// hanging down from it for negative ones. A single-row chart shows both in that row.
+fn draw_signed_chart(f: &mut Frame, area: Rect, block: Block, buf: &MetricBuffer, app: &App) {
+    const UPPER: [&str; 3] = ["▔", "▀", "█"];
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let (start, end) = app.chart_range(buf.len(), (inner.width as usize).div_ceil(2));
+    let peak = buf.range(start..end).fold(0.0, |peak: f64, v| peak.max(v.abs()));
+    if peak == 0.0 || inner.height == 0 {
+        return;
+    }
+    let up_rows = if inner.height == 1 { 1 } else { inner.height / 2 };
+    let down_top = if inner.height == 1 { inner.top() } else { inner.top() + up_rows };
+    let down_rows = inner.bottom() - down_top;
+    let partial = [
+        CUSTOM_SET.empty, CUSTOM_SET.one_eighth, CUSTOM_SET.one_quarter, CUSTOM_SET.three_eighths,
+        CUSTOM_SET.half, CUSTOM_SET.five_eighths, CUSTOM_SET.three_quarters, CUSTOM_SET.seven_eighths,
+    ];
+    let buffer = f.buffer_mut();
+    for (i, value) in buf.range(start..end).enumerate() {
+        let x = inner.left() + 2 * i as u16;
+        if x >= inner.right() {
+            break;
+        }
+        let frac = value.abs() / peak;
+        if *value >= 0.0 {
+            let mut eighths = (frac * up_rows as f64 * 8.0).round() as usize;
+            for row in 0..up_rows {
+                if eighths == 0 {
+                    break;
+                }
+                let symbol = if eighths >= 8 { CUSTOM_SET.full } else { partial[eighths] };
+                buffer.get_mut(x, inner.top() + up_rows - 1 - row).set_symbol(symbol).set_fg(app.theme.bar);
+                eighths = eighths.saturating_sub(8);
+            }
+        } else {
+            // Only three top-anchored block characters exist, so negatives step in thirds of a row
+            let mut thirds = (frac * down_rows as f64 * 3.0).round() as usize;
+            for row in 0..down_rows {
+                if thirds == 0 {
+                    break;
+                }
+                buffer.get_mut(x, down_top + row).set_symbol(UPPER[thirds.min(3) - 1]).set_fg(app.theme.alert);
+                thirds = thirds.saturating_sub(3);
+            }
+        }
+    }
+}
+
+/// Draws 0..1 `values` as bars in the gap column to the right of each chart bar
+fn draw_gap_bars(f: &mut Frame, inner: Rect, values: &[f64], color: Color) {
+    let partial = [
+        CUSTOM_SET.empty, CUSTOM_SET.one_eighth, CUSTOM_SET.one_quarter, CUSTOM_SET.three_eighths,
+        CUSTOM_SET.half, CUSTOM_SET.five_eighths, CUSTOM_SET.three_quarters, CUSTOM_SET.seven_eighths,
+    ];
+    // BarChart gives its labels the bottom row whenever there is more than one
+    let labels = u16::from(inner.height >= 2);
+    let rows = inner.height - labels;
+    let buffer = f.buffer_mut();
+    for (i, value) in values.iter().enumerate() {
+        let x = inner.left() + 2 * i as u16 + 1;
+        if x >= inner.right() {
+            break;
+        }
+        let mut eighths = (value.clamp(0.0, 1.0) * rows as f64 * 8.0).round() as usize;
+        for row in 0..rows {
+            if eighths == 0 {
+                break;
+            }
+            let symbol = if eighths >= 8 { CUSTOM_SET.full } else { partial[eighths] };
+            let cell = buffer.get_mut(x, inner.bottom() - 1 - labels - row);
+            if cell.symbol() == " " {
+                cell.set_symbol(symbol).set_fg(color);
+            }
+            eighths = eighths.saturating_sub(8);
+        }
+    }
+}
+
+/// Draws a horizontal `symbol` line at `level`'s bar height, only into cells the bars
+/// left empty. Used for the EMA trend line and the baseline p50.
+fn draw_level_line(f: &mut Frame, inner: Rect, scaled: &[u64], level: u64, symbol: &str, color: Color) {
+    // BarChart scales every bar against the tallest one, so the line has to as well
+    let max = scaled.iter().copied().max().unwrap_or(0);
+    // A single-row chart has no height to place the line at; it would just fill the gaps
+    if max == 0 || inner.height < 2 {
+        return;
+    }
+    let frac = (level as f64 / max as f64).clamp(0.0, 1.0);
+    let rows_up = ((frac * inner.height as f64).ceil() as u16).clamp(1, inner.height) - 1;
+    let y = inner.bottom() - 1 - rows_up;
+    let buffer = f.buffer_mut();
+    for x in inner.left()..inner.right() {
+        let cell = buffer.get_mut(x, y);
+        if cell.symbol() == " " {
+            cell.set_symbol(symbol).set_fg(color);
+        }
+    }
+}
+
+/// Background for a topology cell: green when idle through yellow to red when saturated
+fn utilisation_color(utilisation: f64) -> Color {
+    let u = utilisation.clamp(0.0, 1.0);
+    let (r, g) = if u < 0.5 { ((u * 2.0 * 255.0) as u8, 200) } else { (255, ((1.0 - u) * 2.0 * 200.0) as u8) };
+    Color::Rgb(r, g, 0)
+}
+
+/// Blue for 0 through green and yellow to red for 1, by sweeping the hue
+fn heat_color(value: f64) -> Color {
+    let hue = (1.0 - value.clamp(0.0, 1.0)) * 4.0;
+    let rising = ((hue % 1.0) * 255.0) as u8;
+    let falling = 255 - rising;
+    match hue as u8 {
+        0 => Color::Rgb(255, rising, 0),
+        1 => Color::Rgb(falling, 255, 0),
+        2 => Color::Rgb(0, 255, rising),
+        3 => Color::Rgb(0, falling, 255),
+        _ => Color::Rgb(0, 0, 255),
+    }
+}
+
+/// All metrics stacked as rows of one-column-per-sample cells, each coloured by the
+/// sample's value relative to that metric's largest sample in the history
+fn draw_heatmap(f: &mut Frame, area: Rect, history: &[MetricBuffer], app: &App) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.border))
+        .title(Span::styled("Heatmap (blue 0 → red max)", Style::default().fg(app.theme.value)))
+        .title_bottom("h / Esc to return");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let order = app.display_order();
+    let label_width = order.iter().map(|&m| app.fields[m].1.chars().count()).max().unwrap_or(0) as u16 + 1;
+    if order.is_empty() || inner.width <= label_width {
+        return;
+    }
+    let rows_per_metric = (inner.height / order.len() as u16).max(1);
+    let len = history.first().map_or(0, |buf| buf.len());
+    let (start, end) = app.chart_range(len, (inner.width - label_width) as usize);
+    let buffer = f.buffer_mut();
+    for (row, &metric) in order.iter().enumerate() {
+        let top = inner.top() + row as u16 * rows_per_metric;
+        if top >= inner.bottom() {
+            break;
+        }
+        let cursor_style = if metric == app.cursor { Style::default().fg(app.theme.focus).add_modifier(Modifier::BOLD) } else { Style::default().fg(app.theme.text) };
+        buffer.set_stringn(inner.left(), top, &app.fields[metric].1, label_width as usize - 1, cursor_style);
+        let buf = &history[metric];
+        let peak = buf.iter().copied().fold(0.0, f64::max);
+        for (i, value) in buf.range(start.min(buf.len())..end.min(buf.len())).enumerate() {
+            let color = heat_color(if peak > 0.0 { value / peak } else { 0.0 });
+            let x = inner.left() + label_width + i as u16;
+            for y in top..(top + rows_per_metric).min(inner.bottom()) {
+                buffer.get_mut(x, y).set_symbol("█").set_fg(color);
+            }
+        }
+    }
+}
+
+/// Grey near r = 0, fading to green towards +1 and red towards -1
+fn correlation_color(r: f64) -> Color {
+    let strength = r.abs().clamp(0.0, 1.0);
+    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * strength) as u8;
+    if r >= 0.0 {
+        Color::Rgb(mix(90, 0), mix(90, 200), mix(90, 0))
+    } else {
+        Color::Rgb(mix(90, 220), mix(90, 0), mix(90, 0))
+    }
+}
+
+/// N×N matrix of Pearson r between the shown metrics over the charted window
+/// (`+` / `-` and `←` / `→` pick it); constant series show `--`
+fn draw_correlation(f: &mut Frame, area: Rect, history: &[MetricBuffer], app: &App) {
+    const CELL: usize = 7;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.border))
+        .title(Span::styled("Correlation (green +1, red -1)", Style::default().fg(app.theme.value)))
+        .title_bottom("C / Esc to return");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let order = app.display_order();
+    let len = history.first().map_or(0, |buf| buf.len());
+    let (start, end) = app.chart_range(len, usize::MAX);
+    let series: Vec<Vec<f64>> = order.iter().map(|&m| history[m].range(start.min(history[m].len())..end.min(history[m].len())).copied().collect()).collect();
+    let label_width = order.iter().map(|&m| app.fields[m].1.chars().count()).max().unwrap_or(0) + 1;
+
+    let mut header = format!("{:width$}", "", width = label_width);
+    for &metric in &order {
+        header.push_str(&format!("{:>width$}", app.fields[metric].1.chars().take(CELL - 1).collect::<String>(), width = CELL));
+    }
+    let mut lines = vec![Line::from(Span::styled(header, Style::default().fg(app.theme.value).add_modifier(Modifier::BOLD)))];
+    for (row, &metric) in order.iter().enumerate() {
+        let mut spans = vec![Span::styled(format!("{:width$}", app.fields[metric].1, width = label_width), Style::default().fg(app.theme.text))];
+        for column in 0..order.len() {
+            let r = pearson(&series[row], &series[column]);
+            spans.push(if r.is_nan() {
+                Span::styled(format!("{:>width$}", "--", width = CELL), Style::default().fg(app.theme.dim))
+            } else {
+                Span::styled(format!("{:>+width$.2}", r, width = CELL), Style::default().fg(Color::Black).bg(correlation_color(r)))
+            });
+        }
+        lines.push(Line::from(spans));
+    }
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Adjacency matrix of the monitored GPUs. NVLink cells are shaded by the row GPU's
+/// busier NVLink direction against its total link capacity, since dcgmi only reports
+/// per-GPU NVLink rates rather than per-peer ones.
+fn draw_topology(f: &mut Frame, area: Rect, app: &App) {
+    const CELL: usize = 12;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.border))
+        .title(Span::styled("NVLink topology", Style::default().fg(app.theme.value)))
+        .title_bottom("t / Esc to return");
+    let topology = match app.topology {
+        Some(Ok(ref topology)) => topology,
+        Some(Err(ref message)) => {
+            let text = Paragraph::new(message.as_str()).block(block).style(Style::default().fg(app.theme.alert)).wrap(Wrap { trim: false });
+            f.render_widget(text, area);
+            return;
+        }
+        None => return,
+    };
+
+    let latest = |field_id: u32, gpu: usize| {
+        let metric = app.fields.iter().position(|(id, _)| *id == field_id)?;
+        app.displayed_history().get(&Entity::gpu(gpu))?[metric].back().copied()
+    };
+    let nvlink_combined = app.fields.iter().position(|(id, _)| *id == 1011).is_some_and(|m| app.columns.get(m).is_some_and(|c| c.len() > 1));
+    // NVLink belongs to the physical GPU, so MIG instances get no row of their own
+    let gpus: Vec<usize> = app.gpus.iter().filter(|e| e.instance.is_none()).map(|e| e.gpu).collect();
+    let mut lines = vec![Line::from(std::iter::once(format!("{:<8}", "")).chain(gpus.iter().map(|g| format!("{:^CELL$}", format!("GPU {}", g)))).collect::<String>())];
+    for &from in &gpus {
+        let capacity = topology.nvlinks_of(from) as f64 * NVLINK_LINK_BYTES_PER_SEC;
+        // NVLBD is the sum of both directions, so its mean direction stands in for the busier one
+        let rate = if nvlink_combined {
+            latest(1011, from).unwrap_or(0.0) / 2.0
+        } else {
+            latest(1011, from).unwrap_or(0.0).max(latest(1012, from).unwrap_or(0.0))
+        };
+        let utilisation = if capacity > 0.0 { rate / capacity } else { 0.0 };
+        let mut spans = vec![Span::styled(format!("{:<8}", format!("GPU {}", from)), Style::default().fg(app.theme.value))];
+        for &to in &gpus {
+            let span = match topology.links.get(&(from, to)) {
+                _ if from == to => Span::styled(format!("{:^CELL$}", "X"), Style::default().fg(app.theme.dim)),
+                Some(Link::NvLink(n)) => Span::styled(
+                    format!("{:^CELL$}", format!("NV{} {:.0}%", n, utilisation * 100.0)),
+                    Style::default().fg(Color::Black).bg(utilisation_color(utilisation)),
+                ),
+                Some(Link::Other(description)) => {
+                    let label = if description.to_ascii_lowercase().contains("pcie") { "PCIe" } else { "other" };
+                    Span::styled(format!("{:^CELL$}", label), Style::default().fg(app.theme.text))
+                }
+                None => Span::styled(format!("{:^CELL$}", "?"), Style::default().fg(app.theme.dim)),
+            };
+            spans.push(span);
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        format!("NVn = n NVLink links; % = busier of NVLTX/NVLRX over n x {} per GPU", format_bytes_with_unit(NVLINK_LINK_BYTES_PER_SEC, true)),
+        Style::default().fg(app.theme.dim),
+    )));
+    f.render_widget(Paragraph::new(lines).block(block).style(Style::default().fg(app.theme.text)), area);
+}
+
+/// Mean and population standard deviation of a buffer
+fn mean_and_stddev(buf: &MetricBuffer) -> (f64, f64) {
+    if buf.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = buf.len() as f64;
+    let mean = buf.iter().sum::<f64>() / n;
+    let variance = buf.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+/// Full-width chart and extended statistics for a single metric
+fn draw_detail(f: &mut Frame, area: Rect, metric: usize, history: &[MetricBuffer], app: &App) {
+    let buf = &history[metric];
+    const DETAIL_PERCENTILES: [f64; 6] = [10.0, 25.0, 50.0, 75.0, 90.0, 99.0];
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .horizontal_margin(1)
+        .constraints([Constraint::Min(5), Constraint::Length(DETAIL_PERCENTILES.len() as u16 + 5)])
+        .split(area);
+
+    let (block, bar_color) = metric_block(metric, buf, app);
+    let charted = app.per_lane_history(history);
+    draw_chart(f, chunks[0], block.title_bottom("Esc to return"), &charted, bar_color, metric, app);
+
+    let kind = metric_kind(app.fields[metric].0);
+    let window = app.stats_window(&charted[metric]);
+    let mut lines: Vec<Line> = if app.diff_gpus.is_some() {
+        let (mean_abs, max) = diff_stats(&window);
+        vec![Line::from(format!("mean |Δ| {}", format_metric_value(kind, mean_abs))), Line::from(format!("max Δ    {}", format_signed(kind, max)))]
+    } else {
+        DETAIL_PERCENTILES.iter().zip(buffer_percentiles(&window, &DETAIL_PERCENTILES))
+            .map(|(pct, value)| Line::from(format!("p{:<4} {}", pct, format_metric_value(kind, value))))
+            .collect()
+    };
+    let (mean, stddev) = mean_and_stddev(&window);
+    lines.push(Line::from(format!("mean  {}", format_metric_value(kind, mean))));
+    lines.push(Line::from(format!("std   {}", format_metric_value(kind, stddev))));
+    lines.push(Line::from(format!("n     {}", window.len())));
+    let stats = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(app.theme.border)).title("Statistics"))
+        .style(Style::default().fg(app.theme.text));
+    f.render_widget(stats, chunks[1]);
+}
+
+fn draw_metric(f: &mut Frame, area: Rect, metric: usize, history: &[MetricBuffer], app: &App) {
+    let buf = &history[metric];
+    // The sparkline benefits from the extra width more than the stats panel does
+    let split = match app.display_mode {
+        DisplayMode::Bar => [Constraint::Percentage(70), Constraint::Percentage(30)],
+        DisplayMode::Sparkline => [Constraint::Percentage(80), Constraint::Percentage(20)],
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(split)
+        .split(area);
+
+    let (block, bar_color) = metric_block(metric, buf, app);
+    let charted = app.per_lane_history(history);
+    let stale = app.stale_for();
+    if stale.is_some() {
+        // Old bars would read as current values, so the chart is hatched until samples return
+        let inner = block.inner(chunks[0]);
+        let hatching = "░".repeat(inner.width as usize);
+        f.render_widget(Paragraph::new(vec![Line::from(hatching); inner.height as usize]).block(block).style(Style::default().fg(app.theme.dim)), chunks[0]);
+    } else {
+        draw_chart(f, chunks[0], block, &charted, bar_color, metric, app);
+    }
+
+    if let Some(ref flash) = app.flash {
+        let color = if flash.is_error { app.theme.alert } else { app.theme.bar };
+        let message = Paragraph::new(flash.message.as_str())
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(app.theme.border)))
+            .style(Style::default().fg(color).add_modifier(Modifier::BOLD));
+        f.render_widget(message, chunks[1]);
+        return;
+    }
+
+    let kind = metric_kind(app.fields[metric].0);
+    let entries: Vec<String> = if app.diff_gpus.is_some() {
+        // Percentiles skip non-positive samples, which would hide every negative difference
+        let (mean_abs, max) = diff_stats(&app.stats_window(&charted[metric]));
+        vec![format!("mean |Δ|: {}", format_metric_value(kind, mean_abs)), format!("max Δ: {}", format_signed(kind, max))]
+    } else {
+        app.percentiles.iter().zip(buffer_percentiles(&app.stats_window(&charted[metric]), &app.percentiles))
+            .map(|(pct, value)| match app.baseline_p50(metric).map(|base| app.per_lane_value(metric, base)) {
+                Some(base) if *pct == 50.0 => format!("p50: {} (base {})", app.format_value(metric, value), app.format_value(metric, base)),
+                _ => format!("p{}: {}", pct, app.format_value(metric, value)),
+            })
+            .collect()
+    };
+    // One line per percentile when the row is tall enough, otherwise squeeze them onto one line
+    let lines: Vec<Line> = if chunks[1].height.saturating_sub(2) as usize >= entries.len() {
+        entries.into_iter().map(Line::from).collect()
+    } else {
+        vec![Line::from(entries.join(" "))]
+    };
+    let mut block = Block::default().borders(Borders::ALL).border_style(Style::default().fg(app.theme.border));
+    if app.window_samples < usize::MAX {
+        block = block.title(format!("last {}", format_window(Duration::from_millis(app.interval_ms * app.window_samples as u64))));
+    }
+    if app.is_clamped(metric, buf) {
+        block = block.title(Span::styled("[clamped]", Style::default().fg(app.theme.warning)));
+    }
+    if let Some(silent) = stale {
+        block = block.title(Span::styled(format!("[stale: {}s]", silent.as_secs()), Style::default().fg(app.theme.alert).add_modifier(Modifier::BOLD)));
+    }
+    let stats = Paragraph::new(lines)
+        .block(block)
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(stats, chunks[1]);
+}
+
+/// --field-doc table; ids outside `FIELD_DOCS` still get a row with their name
+fn print_field_docs(fields: &[(u32, String)]) {
+    let rows: Vec<[String; 5]> = fields
+        .iter()
+        .map(|(id, name)| {
+            let doc = FIELD_DOCS.iter().find(|(doc_id, ..)| doc_id == id);
+            let (dcgm_name, description, unit) = doc.map_or(("", "(not documented here)", ""), |&(_, dcgm, description, unit)| (dcgm, description, unit));
+            [id.to_string(), name.clone(), dcgm_name.to_string(), description.to_string(), unit.to_string()]
+        })
+        .collect();
+    let header = ["FIELD", "NAME", "DCGM NAME", "DESCRIPTION", "UNIT"];
+    let widths: Vec<usize> = (0..header.len())
+        .map(|col| rows.iter().map(|row| row[col].chars().count()).chain([header[col].len()]).max().unwrap_or(0))
+        .collect();
+    let print_row = |cells: [&str; 5]| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        println!("{}", padded.join(" | ").trim_end());
+    };
+    print_row(header);
+    println!("{}", widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("-+-"));
+    for row in &rows {
+        print_row([&row[0], &row[1], &row[2], &row[3], &row[4]]);
+    }
+}
+
+fn print_summary(rows: &[SummaryRow], format: SummaryFormat) {
+    match format {
+        SummaryFormat::Tsv => {
+            println!("gpu\tmetric\tmin\tmean\tp50\tp90\tp99\tmax");
+            for row in rows {
+                println!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", row.gpu, row.metric, row.min, row.mean, row.p50, row.p90, row.p99, row.max);
+            }
+        }
+        SummaryFormat::Json => println!("{}", serde_json::to_string_pretty(rows).unwrap_or_default()),
+    }
+}
+
+/// Everything that can end a run early
+#[derive(Debug)]
+enum AppError {
+    DcgmiNotFound,
+    /// dcgmi (or another source) stopped producing data in a headless run
+    SourceFailed(String),
+    LogFileError(std::io::Error),
+    TerminalError(std::io::Error),
+    ParseError(String),
+    Io(std::io::Error),
+    /// --idle-shutdown ran out after this many idle seconds; exits with status 2
+    Idle(u64),
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AppError::DcgmiNotFound => write!(f, "dcgmi was not found in PATH; is DCGM installed?"),
+            AppError::SourceFailed(message) => write!(f, "{}", message),
+            AppError::LogFileError(e) => write!(f, "cannot open log file: {}", e),
+            AppError::TerminalError(e) => write!(f, "terminal error: {}", e),
+            AppError::ParseError(message) => write!(f, "{}", message),
+            AppError::Io(e) => write!(f, "{}", e),
+            AppError::Idle(secs) => write!(f, "GPUs idle for over {}s; shut down by --idle-shutdown", secs),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::LogFileError(e) | AppError::TerminalError(e) | AppError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e)
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(if matches!(e, AppError::Idle(_)) { 2 } else { 1 });
+    }
+}
+
+/// The next terminal event, waiting up to 10ms for one
+fn next_event() -> std::io::Result<Option<Event>> {
+    if event::poll(Duration::from_millis(10))? {
+        event::read().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Shows the frames of a --record file at the pace they were drawn, holding the last one until q
+fn play_recording(path: &str) -> Result<(), AppError> {
+    let frames = read_frames(path).map_err(AppError::from)?;
+    let Some(first) = frames.first().map(|frame| frame.timestamp_ms) else {
+        return Err(AppError::ParseError(format!("{} holds no frames", path)));
+    };
+    enable_raw_mode().map_err(AppError::TerminalError)?;
+    let mut stdout = std::io::stdout();
+    execute!(stdout, EnterAlternateScreen).map_err(AppError::TerminalError)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout)).map_err(AppError::TerminalError)?;
+
+    let mut started = Instant::now();
+    let mut paused_at: Option<Instant> = None;
+    let mut next = 0;
+    loop {
+        if paused_at.is_none() {
+            let mut due = None;
+            while next < frames.len() && started.elapsed() >= Duration::from_millis(frames[next].timestamp_ms.saturating_sub(first)) {
+                due = Some(&frames[next].buffer);
+                next += 1;
+            }
+            // A terminal smaller than the recording shows its top-left corner
+            if let Some(recorded) = due {
+                terminal.draw(|f| {
+                    let area = f.size().intersection(recorded.area);
+                    let buffer = f.buffer_mut();
+                    for y in area.top()..area.bottom() {
+                        for x in area.left()..area.right() {
+                            *buffer.get_mut(x, y) = recorded.get(x, y).clone();
+                        }
+                    }
+                }).map_err(AppError::TerminalError)?;
+            }
+        }
+        if event::poll(Duration::from_millis(10)).map_err(AppError::TerminalError)? {
+            if let Event::Key(key) = event::read().map_err(AppError::TerminalError)? {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Char(' ') => match paused_at.take() {
+                        Some(at) => started += at.elapsed(),
+                        None => paused_at = Some(Instant::now()),
+                    },
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    disable_raw_mode().map_err(AppError::TerminalError)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen).map_err(AppError::TerminalError)?;
+    terminal.show_cursor().map_err(AppError::TerminalError)
+}
+
+fn run() -> Result<(), AppError> {
+    let args = Args::from_config_and_cli()?;
+    if let Some(ref path) = args.play {
+        return play_recording(path);
+    }
+    let frame_time = Duration::from_millis(1000 / args.max_fps.max(1));
+    let mut fields: Vec<(u32, String)> = if args.fields.is_empty() {
+        DEFAULT_FIELDS.iter().map(|(id, name)| (*id, name.to_string())).collect()
+    } else {
+        args.fields
+    };
+    if args.gpu_clock {
+        let missing: Vec<(u32, String)> = CLOCK_FIELDS.iter().filter(|(id, _)| !fields.iter().any(|(f, _)| f == id)).map(|(id, name)| (*id, name.to_string())).collect();
+        fields.extend(missing);
+    }
+    if !args.filter.is_empty() {
+        let all: Vec<String> = fields.iter().map(|(_, name)| name.clone()).collect();
+        let keep = args.filter.iter().map(|name| metric_index(&all, name.trim())).collect::<Result<HashSet<_>, _>>().map_err(AppError::ParseError)?;
+        // Definition order is kept regardless of the order names were listed in
+        fields = fields.into_iter().enumerate().filter(|(i, _)| keep.contains(i)).map(|(_, field)| field).collect();
+    }
+    if args.field_doc {
+        print_field_docs(&fields);
+        return Ok(());
+    }
+    // NVML stands in for a missing dcgmi when it loads; --nvml-fallback insists on it
+    let live = !args.mock && args.replay.is_none() && args.input_file.is_none();
+    let nvml = if args.nvml_fallback {
+        Some(NvmlReader::init().map_err(|e| AppError::SourceFailed(format!("--nvml-fallback: cannot load NVML: {}", e)))?)
+    } else if live && args.group_id.is_none() && !args.mig && !in_path("dcgmi") {
+        NvmlReader::init().ok()
+    } else {
+        None
+    };
+    if nvml.is_some() {
+        let (kept, unreadable): (Vec<Field>, Vec<Field>) = fields.into_iter().partition(|(id, _)| NVML_FIELDS.contains(id));
+        if !unreadable.is_empty() {
+            eprintln!("warning: NVML cannot read {}; leaving them out", unreadable.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>().join(", "));
+        }
+        if kept.is_empty() {
+            return Err(AppError::ParseError("none of the requested fields can be read through NVML".to_string()));
+        }
+        fields = kept;
+    }
+    // dcgmi (and --mock) still report every raw field; the shown metrics may sum some of them
+    let dcgmi_fields = fields.clone();
+    let pairs: Vec<(u32, u32, &str)> = [(args.combine_nvlink, NVLINK_PAIR), (args.combine_pcie, PCIE_PAIR)]
+        .into_iter().filter(|(on, _)| *on).map(|(_, pair)| pair).collect();
+    let (mut fields, columns) = combine_fields(&fields, &pairs).map_err(AppError::ParseError)?;
+    // EFFIC needs SMACT, FB_USED and each GPU's total memory (40 GB, like the mock's FB_USED, under --mock)
+    let efficiency = match (fields.iter().position(|(id, _)| *id == 1002), fields.iter().position(|(id, _)| *id == 252)) {
+        (Some(smact), Some(fb_used)) => {
+            let total_vram = if args.mock { args.gpus.iter().map(|&gpu| (gpu, 40960.0)).collect() } else { query_total_memory() };
+            (!total_vram.is_empty()).then_some(Efficiency { smact, fb_used, total_vram })
+        }
+        _ => None,
+    };
+    if efficiency.is_some() {
+        fields.push((EFFIC_FIELD, "EFFIC".to_string()));
+    }
+    let stacked: Vec<(usize, usize)> = [NVLINK_PAIR, PCIE_PAIR].iter()
+        .filter_map(|&(tx, rx, _)| Some((fields.iter().position(|(id, _)| *id == tx)?, fields.iter().position(|(id, _)| *id == rx)?)))
+        .collect();
+    if args.stacked_bar && stacked.is_empty() {
+        return Err(AppError::ParseError("--stacked-bar needs NVLTX and NVLRX or PCITX and PCIRX to be monitored".to_string()));
+    }
+    let stacked = if args.stacked_bar { stacked } else { Vec::new() };
+    let idle = match (args.idle_shutdown, fields.iter().position(|(id, _)| *id == 1002)) {
+        (false, _) => None,
+        (true, Some(smact)) => Some(IdleShutdown::new(smact, args.gpu_util_threshold, Duration::from_secs(args.idle_duration))),
+        (true, None) => return Err(AppError::ParseError("--idle-shutdown watches SMACT (field 1002), which is not monitored".to_string())),
+    };
+    // Metrics dcgmi measures, which replay and baseline files are read against, and those logged
+    let measured = fields.len() - usize::from(efficiency.is_some());
+    let logged = if args.log_derived { fields.len() } else { measured };
+    let names: Vec<String> = fields.iter().map(|(_, name)| name.clone()).collect();
+    // Titles and log columns use the aliases; every option naming a metric uses `names`
+    let mut display_names = names.clone();
+    for (name, alias) in &args.field_aliases {
+        display_names[metric_index(&names, name).map_err(AppError::ParseError)?] = alias.clone();
+    }
+    if let Some(name) = display_names.iter().enumerate().find(|(i, name)| display_names[..*i].contains(name)).map(|(_, name)| name) {
+        return Err(AppError::ParseError(format!("--field-alias gives two metrics the name `{}`", name)));
+    }
+    for ((_, name), display) in fields.iter_mut().zip(&display_names) {
+        name.clone_from(display);
+    }
+    let alerts = args.alerts.iter().map(|s| parse_alert_rule(s, &names)).collect::<Result<Vec<_>, _>>().map_err(AppError::ParseError)?;
+    let percentile_alarms = args.percentile_alarms.iter().map(|s| parse_percentile_alarm(s, &names)).collect::<Result<Vec<_>, _>>().map_err(AppError::ParseError)?;
+    // Ratios get the global levels by default; other kinds only colour when configured
+    let mut thresholds: Vec<Option<Thresholds>> = fields.iter()
+        .map(|(id, _)| (metric_kind(*id) == MetricKind::Ratio).then_some(Thresholds { warn: args.warn_threshold, critical: args.critical_threshold }))
+        .collect();
+    for (name, levels) in &args.metric_thresholds {
+        thresholds[metric_index(&names, name).map_err(AppError::ParseError)?] = Some(*levels);
+    }
+    let mut max_values = vec![None; fields.len()];
+    for (name, max) in &args.max_values {
+        max_values[metric_index(&names, name).map_err(AppError::ParseError)?] = Some(*max);
+    }
+    // Derived metrics are computed from the unscaled samples and cannot take a factor either
+    let mut scales = vec![1.0; measured];
+    for (name, factor) in &args.metric_scales {
+        let metric = metric_index(&names, name).map_err(AppError::ParseError)?;
+        if metric >= measured {
+            return Err(AppError::ParseError(format!("--metric-scale: `{}` is derived from other metrics and cannot be scaled", name)));
+        }
+        scales[metric] = *factor;
+    }
+    // Derived metrics keep their place after the measured ones
+    let field_order = if args.field_order.is_empty() {
+        None
+    } else {
+        let mut rank: Vec<Option<usize>> = vec![None; fields.len()];
+        for (position, name) in args.field_order.iter().enumerate() {
+            let metric = metric_index(&names[..measured], name).map_err(|e| AppError::ParseError(format!("--field-order: {}", e)))?;
+            if rank[metric].replace(position).is_some() {
+                return Err(AppError::ParseError(format!("--field-order names `{}` twice", name)));
+            }
+        }
+        if let Some(missing) = names[..measured].iter().zip(&rank).find(|(_, rank)| rank.is_none()).map(|(name, _)| name) {
+            return Err(AppError::ParseError(format!("--field-order must name every metric; `{}` is missing", missing)));
+        }
+        Some(rank.into_iter().enumerate().map(|(i, rank)| rank.unwrap_or(i)).collect())
+    };
+    let mut units = vec![None; fields.len()];
+    for (name, unit) in &args.metric_units {
+        units[metric_index(&names, name).map_err(AppError::ParseError)?] = Some(unit.clone());
+    }
+    // Clocks are charted against the rated boost clock unless --max-value says otherwise
+    let unscaled_clock = |id: u32| fields.iter().zip(&max_values).any(|((f, _), max)| *f == id && max.is_none());
+    if unscaled_clock(100) || unscaled_clock(101) {
+        // An A100's rated clocks stand in for nvidia-smi under --mock
+        let rated = if args.mock { Some((1980.0, 1593.0)) } else { query_max_clocks() };
+        if let Some((sm, mem)) = rated {
+            for ((id, _), max) in fields.iter().zip(max_values.iter_mut()) {
+                match id {
+                    100 => *max = max.or(Some(sm)),
+                    101 => *max = max.or(Some(mem)),
+                    _ => {}
+                }
+            }
+        }
+    }
+    let mut delta = vec![args.delta && args.delta_metrics.is_empty(); fields.len()];
+    for name in &args.delta_metrics {
+        delta[metric_index(&names, name.trim()).map_err(AppError::ParseError)?] = true;
+    }
+    let field_ids = dcgmi_fields.iter().map(|(id, _)| id.to_string()).collect::<Vec<_>>().join(",");
+    if args.no_tui && args.log_file.is_none() && args.log_dir.is_none() && !args.headless_summary {
+        return Err(AppError::ParseError("--no-tui needs --log, --log-dir or --headless-summary".to_string()));
+    }
+    if args.rotate_log.is_some() && args.output_format == OutputFormat::Parquet {
+        return Err(AppError::ParseError("--rotate-log cannot be used with parquet output".to_string()));
+    }
+    if args.rotate_log.is_some_and(|mb| mb <= 0.0 || !mb.is_finite()) {
+        return Err(AppError::ParseError("--rotate-log must be a positive number of megabytes".to_string()));
+    }
+    if args.error_threshold == Some(0) {
+        return Err(AppError::ParseError("--error-threshold must be at least 1".to_string()));
+    }
+    if args.csv_append && args.output_format == OutputFormat::Parquet {
+        return Err(AppError::ParseError("--csv-append cannot be used with parquet output".to_string()));
+    }
+    if args.csv_timestamp_format.format(chrono::Local::now()).contains(args.csv_delimiter) {
+        return Err(AppError::ParseError("--csv-delimiter appears in the --csv-timestamp-format timestamps".to_string()));
+    }
+    // Groups report their GPUs at runtime, so only an explicit --gpus list can be checked
+    let monitored = |id: usize| args.group_id.is_some() || args.mig || args.gpus.contains(&id);
+    let diff_gpus = match args.diff_gpu[..] {
+        [] => None,
+        [a, b] if a != b && monitored(a) && monitored(b) => Some((Entity::gpu(a), Entity::gpu(b))),
+        [a, b] if a != b => return Err(AppError::ParseError(format!("--diff-gpu {},{} needs both GPUs in --gpus", a, b))),
+        _ => return Err(AppError::ParseError("--diff-gpu takes two different GPU ids, e.g. --diff-gpu 0,1".to_string())),
+    };
+    let dual_gpus = match args.dual_gpu_compare[..] {
+        [] => None,
+        [a, b] if a != b && monitored(a) && monitored(b) => Some((Entity::gpu(a), Entity::gpu(b))),
+        [a, b] if a != b => return Err(AppError::ParseError(format!("--dual-gpu-compare {},{} needs both GPUs in --gpus", a, b))),
+        _ => return Err(AppError::ParseError("--dual-gpu-compare takes two different GPU ids, e.g. --dual-gpu-compare 0,1".to_string())),
+    };
+    if args.checkpoint == Some(0) {
+        return Err(AppError::ParseError("--checkpoint must be at least 1 second".to_string()));
+    }
+    if args.checkpoint.is_some() {
+        std::fs::create_dir_all(&args.checkpoint_dir).map_err(AppError::from)?;
+    }
+    if args.smooth == Some(0) {
+        return Err(AppError::ParseError("--smooth must be at least 1 sample".to_string()));
+    }
+    if args.nvlink_lanes == Some(0) {
+        return Err(AppError::ParseError("--nvlink-lanes must be at least 1".to_string()));
+    }
+    if args.min_bar_height.is_some_and(|pct| !(pct > 0.0 && pct <= 100.0)) {
+        return Err(AppError::ParseError("--min-bar-height must be a percentage above 0 and at most 100".to_string()));
+    }
+    match args.aggregate_period {
+        Some(0) => return Err(AppError::ParseError("--aggregate-period must be at least 1 second".to_string())),
+        Some(_) if args.log_file.is_none() && args.log_dir.is_none() => return Err(AppError::ParseError("--aggregate-period needs --log or --log-dir".to_string())),
+        Some(_) if args.output_format != OutputFormat::Csv => return Err(AppError::ParseError("--aggregate-period only writes CSV logs".to_string())),
+        _ => {}
+    }
+    if args.top_n == Some(0) {
+        return Err(AppError::ParseError("--top-n must be at least 1".to_string()));
+    }
+    if args.resample_hz.is_some_and(|hz| hz <= 0.0 || !hz.is_finite()) {
+        return Err(AppError::ParseError("--resample-hz must be a positive number of samples per second".to_string()));
+    }
+    // Charts, windows and the ring log count samples, which arrive at the resampled rate when there is one
+    let sample_interval_ms = args.resample_hz.map_or(args.interval_ms, |hz| (1000.0 / hz).round().max(1.0) as u64);
+    if args.rate_limit.is_some_and(|rate| rate <= 0.0 || !rate.is_finite()) {
+        return Err(AppError::ParseError("--rate-limit must be a positive number of samples per second".to_string()));
+    }
+    let log_options = LogOptions {
+        format: args.output_format,
+        timestamp: args.csv_timestamp_format.clone(),
+        label: args.label.clone(),
+        session_id: Some(args.session_id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string())),
+        delimiter: args.csv_delimiter,
+        header: !args.csv_no_header,
+        row_group_size: args.parquet_row_group_size,
+        rotation: None,
+        aggregate_period: args.aggregate_period.map(Duration::from_secs),
+    };
+    let logger: Option<Logger> = match args.log_file {
+        Some(ref path) => {
+            let (file, empty) = open_log_file(path, args.csv_append).map_err(AppError::LogFileError)?;
+            let options = LogOptions {
+                rotation: args.rotate_log.map(|mb| Rotation { path: path.clone(), max_bytes: (mb * 1024.0 * 1024.0) as u64, keep: args.rotate_keep }),
+                ..log_options.clone()
+            };
+            Some(spawn_logger_thread(file, display_names[..logged].to_vec(), empty && !args.csv_no_header, options).map_err(AppError::LogFileError)?)
+        }
+        None => None,
+    };
+    let mut log_dir = match args.log_dir {
+        Some(ref dir) => {
+            let session = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
+            Some(LogDir::create(dir, session, display_names[..logged].to_vec(), log_options).map_err(AppError::LogFileError)?)
+        }
+        None => None,
+    };
+    let ring_logger = match args.samplelog_bin {
+        Some(ref path) => Some(spawn_ring_logger_thread(
+            RingWriter::create(path, &display_names[..logged], sample_interval_ms, args.samplelog_bin_records).map_err(AppError::LogFileError)?,
+        )),
+        None => None,
+    };
+    let webhook = args.alert_webhook.clone().or_else(|| std::env::var("DCGMI_TUI_WEBHOOK").ok().filter(|url| !url.is_empty()));
+    let mut alert_log = match args.alert_log {
+        Some(path) => Some(std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(AppError::LogFileError)?),
+        None => None,
+    };
+    // Without a CSV/JSONL log to hold them, annotations get a text file of their own
+    let annotation_path = match (&args.log_file, &args.log_dir) {
+        (Some(path), _) => format!("{}.annotations.txt", path),
+        (None, Some(dir)) => format!("{}/annotations.txt", dir),
+        (None, None) => "dcgmi_annotations.txt".to_string(),
+    };
+    let mut stderr_log = match args.stderr_log {
+        Some(ref path) => Some(std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(AppError::LogFileError)?),
+        None => None,
+    };
+    // MIG instances only exist inside a group, so --mig watches one and discovers them
+    let group_id = args.group_id.or(args.mig.then_some(0));
+    let target: Vec<String> = if let Some(group) = group_id {
+        vec!["--group-id".to_string(), group.to_string()]
+    } else {
+        vec!["--entity-id".to_string(), args.gpus.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",")]
+    };
+    let replay = args.replay.as_deref().map(|path| Replay::load(path, args.replay_speed.unwrap_or(1.0), &display_names[..measured])).transpose().map_err(AppError::from)?;
+
+    let baseline = args.baseline_file.as_deref().map(|path| load_baseline(path, &display_names[..measured])).transpose().map_err(AppError::from)?;
+    let gpu_names = match (args.gpu_name, args.mock) {
+        (false, _) => BTreeMap::new(),
+        (true, true) => args.gpus.iter().map(|&id| (id, "Mock GPU".to_string())).collect(),
+        (true, false) => query_gpu_names(),
+    };
+    // Queried once up front; the topology does not change while the tool runs
+    let topology = match (args.topo, args.mock) {
+        (false, _) => None,
+        (true, true) => Some(Ok(Topology::full_mesh(&args.gpus, 12))),
+        (true, false) => Some(query_topology(&args.gpus)),
+    };
+
+    let latest = (args.prometheus_port.is_some() || args.socket.is_some()).then(|| Arc::new(Mutex::new(LatestStats::default())));
+    if let (Some(port), Some(state)) = (args.prometheus_port, &latest) {
+        spawn_prometheus_server(args.prometheus_bind, port, Arc::clone(state)).map_err(AppError::from)?;
+    }
+    if let (Some(path), Some(state)) = (&args.socket, &latest) {
+        spawn_socket_server(path, Arc::clone(state)).map_err(AppError::from)?;
+    }
+
+    if let Some(pid) = args.watch_pid.filter(|&pid| !process_alive(pid)) {
+        return Err(AppError::SourceFailed(format!("--watch-pid: no running process with PID {}", pid)));
+    }
+    let pid_exit = args.watch_pid.map(spawn_pid_watcher);
+
+    // Ctrl-C arrives as a key press in raw mode, so the handler mainly serves SIGTERM and --no-tui
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&shutdown);
+    ctrlc::set_handler(move || handler_flag.store(true, AtomicOrdering::SeqCst))
+        .map_err(|e| AppError::Io(std::io::Error::other(e)))?;
+    // Opened before the terminal is taken over so a bad path is reported readably
+    let mut recorder = args.record.as_deref().map(FrameWriter::create).transpose().map_err(AppError::from)?;
+    let input = match args.input_file {
+        Some(ref path) => {
+            let pace = args.replay_speed.map(|speed| Duration::from_millis(args.interval_ms).div_f64(speed.max(0.001)));
+            Some(spawn_file_reader(path, pace).map_err(AppError::from)?)
+        }
+        None => None,
+    };
+
+    let mut terminal = if args.no_tui {
+        None
+    } else {
+        enable_raw_mode().map_err(AppError::TerminalError)?;
+        let mut stdout = std::io::stdout();
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture).map_err(AppError::TerminalError)?;
+        Some(Terminal::new(CrosstermBackend::new(stdout)).map_err(AppError::TerminalError)?)
+    };
+
+    let mut app = match replay {
+        Some(replay) => {
+            let mut app = App::new(fields, replay.gpus(), args.history_len);
+            app.replay = Some(replay);
+            app
+        }
+        None => {
+            let mut entities: Vec<Entity> = args.gpus.iter().map(|&id| Entity::gpu(id)).collect();
+            // Synthetic data has no real partitions to discover, so invent two per GPU
+            if args.mig && args.mock {
+                entities = entities.iter().flat_map(|e| [*e, Entity { instance: Some(0), ..*e }, Entity { instance: Some(1), ..*e }]).collect();
+            }
+            App::new(fields, entities, args.history_len)
+        }
+    };
+    app.alerts = alerts;
+    app.percentile_alarms = percentile_alarms;
+    app.percentiles = args.percentiles;
+    app.theme = args.theme.theme();
+    app.aggregate = args.aggregate;
+    app.diff_gpus = diff_gpus;
+    app.dual_gpus = dual_gpus;
+    app.sort_mode = args.sort;
+    app.field_order = field_order;
+    app.top_n = args.top_n;
+    app.watchface = args.watchface;
+    app.draw_times = args.benchmark.then(|| VecDeque::with_capacity(BENCHMARK_DRAWS));
+    app.fps_history = args.fps_chart.then(|| VecDeque::with_capacity(FPS_HISTORY));
+    app.metric_groups = args.metric_group;
+    app.stacked = stacked;
+    if args.heatmap {
+        app.view = AppView::Heatmap;
+    }
+    if args.correlation {
+        app.view = AppView::Correlation;
+    }
+    app.topology = topology;
+    app.gpu_names = gpu_names;
+    app.discover_entities = group_id.is_some() && !args.mock;
+    if app.discover_entities {
+        app.placeholders = app.gpus.iter().copied().collect();
+    }
+    app.group_id = args.group_id;
+    app.baseline = baseline;
+    app.thresholds = thresholds;
+    app.temp_ranges = args.temp_ranges;
+    app.aliases = names.iter().zip(&display_names).filter(|(name, alias)| name != alias).map(|(name, alias)| (name.clone(), alias.clone())).collect();
+    app.max_values = max_values;
+    app.scales = scales;
+    app.units = units;
+    app.nvlink_lanes = args.nvlink_lanes;
+    app.delta = delta;
+    app.baseline_tolerance = args.baseline_tolerance;
+    app.ema_alpha = args.ema_alpha.clamp(0.0, 1.0);
+    app.smooth = args.smooth.unwrap_or(1);
+    app.graph_style = args.graph_style;
+    app.silent_start = args.silent_start.map(Duration::from_secs);
+    app.min_bar_height = args.min_bar_height;
+    app.min_bar_colour = args.min_bar_height_colour;
+    app.interval_ms = sample_interval_ms;
+    if let Some(secs) = args.rolling_window {
+        app.window_samples = (secs * 1000 / app.interval_ms).max(1) as usize;
+    }
+    app.duration = args.duration.map(Duration::from_secs);
+    app.sample_limit = args.sample_count;
+    app.watch_pid = args.watch_pid;
+    app.stderr_in_footer = args.stderr_log.is_none();
+    app.min_sample_gap = args.rate_limit.map(|rate| Duration::from_secs_f64(1.0 / rate));
+    app.show_parse_errors = args.error_threshold.is_some();
+    app.warn_missing = args.warn_on_missing_samples;
+    app.raw_field_count = dcgmi_fields.len();
+    if !pairs.is_empty() {
+        app.columns = columns;
+    }
+    app.efficiency = efficiency;
+    app.auto_interval = args.interval_auto;
+    app.idle = idle;
+
+    let process_updates = args.show_procs.then(|| spawn_process_poller(app.gpus.iter().filter(|e| e.instance.is_none()).map(|e| e.gpu).collect(), args.mock));
+    if args.show_procs {
+        app.processes = Some(BTreeMap::new());
+        app.show_processes = true;
+    }
+
+    // Only live sessions are persisted; recorded or synthetic data would pollute the next launch
+    let session = if app.replay.is_none() && !args.mock && args.input_file.is_none() { session_path() } else { None };
+    if !args.no_restore {
+        if let Some(saved) = session.as_deref().and_then(load_session) {
+            app.restore_session(saved);
+        }
+    }
+
+    // Replay, mock and input files stand in for a live source, so none is started
+    let mut live_source: Option<Box<dyn MetricSource>> = None;
+    let mut source: Option<Receiver<SourceEvent>> = None;
+    let resampled = |rx: Receiver<SourceEvent>| match args.resample_hz {
+        Some(hz) => spawn_resampler(rx, hz, args.resample_interpolation),
+        None => rx,
+    };
+    if args.mock {
+        source = Some(resampled(spawn_mock(&dcgmi_fields, &app.gpus, args.interval_ms)));
+    } else if input.is_some() {
+        source = input.map(resampled);
+    } else if app.replay.is_none() {
+        let mut live: Box<dyn MetricSource> = match nvml {
+            Some(reader) => Box::new(NvmlSource {
+                reader: Arc::new(reader),
+                field_ids: dcgmi_fields.iter().map(|(id, _)| *id).collect(),
+                gpus: app.gpus.clone(),
+                stop: Arc::new(AtomicBool::new(false)),
+                handle: None,
+            }),
+            None => Box::new(DcgmiSource { field_ids: field_ids.clone(), target: target.clone(), child: None }),
+        };
+        match live.start(args.interval_ms) {
+            Ok(rx) => {
+                source = Some(resampled(rx));
+                live_source = Some(live);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                app.state = AppState::Error(AppError::DcgmiNotFound.to_string());
+            }
+            Err(e) => app.state = AppState::Error(format!("Failed to start {}: {}", live.name(), e)),
+        }
+    }
+    let mut stdout_closed = false;
+    // Watchdog state: when the next respawn is due and how many have happened
+    let mut restart_at: Option<Instant> = None;
+    let mut restarts: u32 = 0;
+    let mut failure: Option<AppError> = None;
+    let mut last_render = Instant::now();
+    let mut watched_exited = false;
+    // PNG exports still being written by their background threads
+    let mut png_exports: Vec<thread::JoinHandle<Result<String, String>>> = Vec::new();
+    // When --trigger-cmd last ran for each alert rule
+    let mut last_triggered: BTreeMap<usize, Instant> = BTreeMap::new();
+    let checkpoint_period = args.checkpoint.map(Duration::from_secs);
+    let mut next_checkpoint = checkpoint_period.map(|period| Instant::now() + period);
+    let mut auto_interval = args.interval_auto.then(|| AutoInterval::new(args.target_cpu, args.min_interval, args.max_interval));
+
+    loop {
+        if shutdown.load(AtomicOrdering::SeqCst) || app.limit_reached() || failure.is_some() {
+            break;
+        }
+        if pid_exit.as_ref().is_some_and(|rx| rx.try_recv().is_ok()) {
+            watched_exited = true;
+            break;
+        }
+        match app.idle.as_mut().and_then(|idle| idle.update(&app.history)) {
+            Some(IdleEvent::Warned) => {
+                let message = format!("GPUs idle for {}s (SMACT under {}); exiting in {}s", args.idle_duration, args.gpu_util_threshold, IDLE_GRACE.as_secs());
+                if let Some(ref mut file) = alert_log {
+                    writeln!(file, "{},{}", chrono::Local::now().to_rfc3339(), message).ok();
+                }
+                if terminal.is_none() {
+                    eprintln!("warning: {}", message);
+                } else {
+                    app.flash(format!("{} unless a key is pressed", message), true);
+                }
+            }
+            Some(IdleEvent::Expired) => {
+                failure = Some(AppError::Idle(args.idle_duration));
+                break;
+            }
+            None => {}
+        }
+        // Without a screen to show it on, a failed source ends a headless run
+        if terminal.is_none() {
+            if let AppState::Error(ref message) = app.state {
+                failure = Some(AppError::SourceFailed(message.clone()));
+                break;
+            }
+        }
+        app.advance_replay();
+        if let Some(processes) = process_updates.as_ref().and_then(|rx| rx.try_iter().last()) {
+            app.processes = Some(processes);
+        }
+        if let Some(ref rx) = source {
+            while let Ok(event) = rx.try_recv() {
+                match event {
+                    // All of a tick's GPUs land before the next frame, so no frame shows half a tick
+                    SourceEvent::Tick(lines) => {
+                        for line in lines {
+                            if let Some(mut sample) = app.ingest_line(&line) {
+                                if args.log_derived {
+                                    sample.1 = app.with_derived(sample.0, &sample.1);
+                                }
+                                if let Some(ref ring) = ring_logger {
+                                    ring.send(sample.clone());
+                                }
+                                if let Some(ref mut logs) = log_dir {
+                                    if let Err(e) = logs.send(sample.clone()) {
+                                        failure.get_or_insert(AppError::LogFileError(e));
+                                    }
+                                }
+                                if let Some(ref logger) = logger {
+                                    logger.send(sample);
+                                }
+                            }
+                            // Stop mid-batch so exactly --sample-count rows are logged
+                            if app.limit_reached() {
+                                break;
+                            }
+                        }
+                        // A run of bad lines usually means dmon's output format changed
+                        if args.error_threshold.is_some_and(|n| app.consecutive_parse_errors >= n) {
+                            failure = Some(AppError::ParseError(format!(
+                                "{} consecutive dcgmi lines could not be parsed; last one: {}", app.consecutive_parse_errors, app.last_raw_line)));
+                            break;
+                        }
+                        for (gpu_id, idx, value) in app.evaluate_percentile_alarms() {
+                            let message = format!("{},gpu {},{},value={}", chrono::Local::now().to_rfc3339(), gpu_id, app.percentile_alarms[idx].describe(&names), value);
+                            // BEL on stdout reaches the terminal even while the TUI owns it
+                            print!("\x07");
+                            std::io::stdout().flush().ok();
+                            // Writing to a terminal stderr would scribble over the TUI
+                            if terminal.is_none() || !std::io::stderr().is_terminal() {
+                                eprintln!("{}", message);
+                            }
+                            if terminal.is_some() {
+                                app.flash(format!("Alarm: {}", app.percentile_alarms[idx].describe(&names)), true);
+                            }
+                        }
+                        if app.limit_reached() {
+                            break;
+                        }
+                    }
+                    SourceEvent::Stdout(line) => app.last_raw_line = line,
+                    SourceEvent::Stderr(line) => {
+                        if let Some(ref mut file) = stderr_log {
+                            writeln!(file, "{}", line).ok();
+                        }
+                        // Still kept in memory for the error screen if dcgmi dies
+                        app.push_stderr(line);
+                    }
+                    SourceEvent::Closed => {
+                        stdout_closed = true;
+                        // A finished input file is expected to end; only a dying live source is an error
+                        app.at_eof = live_source.is_none();
+                    }
+                }
+            }
+        }
+        if stdout_closed && matches!(app.state, AppState::Running) && restart_at.is_none() {
+            if let Some(ref mut live) = live_source {
+                match live.exit_status() {
+                    Ok(Some(_)) if args.watchdog_restart && args.watchdog_max_restarts.is_none_or(|max| restarts < max) => {
+                        restart_at = Some(Instant::now() + WATCHDOG_DELAY);
+                    }
+                    Ok(Some(status)) => {
+                        app.state = AppState::Error(format!("{} exited unexpectedly ({})", live.name(), status));
+                    }
+                    Ok(None) => {}
+                    // Breaking rather than returning lets the shutdown below restore the terminal
+                    Err(e) => {
+                        failure = Some(AppError::from(e));
+                        break;
+                    }
+                }
+            }
+        }
+        if let (true, Some(ref mut live)) = (restart_at.is_some_and(|at| Instant::now() >= at), &mut live_source) {
+            restart_at = None;
+            restarts += 1;
+            // Under --resample-hz `app.interval_ms` is the grid's period, not dcgmi's
+            let interval = if args.resample_hz.is_some() { args.interval_ms } else { app.interval_ms };
+            match live.start(interval) {
+                Ok(rx) => {
+                    source = Some(resampled(rx));
+                    stdout_closed = false;
+                    app.flash = Some(Flash { message: "[RESTARTED]".to_string(), is_error: false, cycles_left: RESTART_FLASH_CYCLES });
+                }
+                Err(e) => app.state = AppState::Error(format!("Failed to restart {}: {}", live.name(), e)),
+            }
+        }
+
+        if let Some(interval) = auto_interval.as_mut().and_then(|auto| auto.adjust(app.interval_ms)) {
+            app.interval_ms = interval;
+            if let Some(secs) = args.rolling_window {
+                app.window_samples = (secs * 1000 / interval).max(1) as usize;
+            }
+            // dmon cannot change its interval in place, so the source is restarted with the new one
+            if args.mock {
+                source = Some(spawn_mock(&dcgmi_fields, &app.gpus, interval));
+            } else if let Some(ref mut live) = live_source {
+                match live.start(interval) {
+                    Ok(rx) => {
+                        source = Some(rx);
+                        stdout_closed = false;
+                    }
+                    Err(e) => app.state = AppState::Error(format!("Failed to restart {}: {}", live.name(), e)),
+                }
+            }
+        }
+
+        if let (Some(at), Some(period)) = (next_checkpoint, checkpoint_period) {
+            if Instant::now() >= at {
+                next_checkpoint = Some(at + period);
+                if let Err(e) = app.save_checkpoint(&args.checkpoint_dir, args.checkpoint_keep) {
+                    if terminal.is_some() {
+                        app.flash(format!("Checkpoint failed: {}", e), true);
+                    } else {
+                        eprintln!("Checkpoint failed: {}", e);
+                    }
+                }
+            }
+        }
+
+        while let Some(done) = png_exports.iter().position(|handle| handle.is_finished()) {
+            match png_exports.remove(done).join() {
+                Ok(Ok(path)) => app.flash(format!("Saved {}", path), false),
+                Ok(Err(e)) => app.flash(format!("PNG export failed: {}", e), true),
+                Err(_) => app.flash("PNG export failed".to_string(), true),
+            }
+        }
+
+        if last_render.elapsed() >= frame_time {
+            if let Some(ref state) = latest {
+                app.publish_latest(state);
+            }
+            for (gpu_id, idx) in app.evaluate_alerts() {
+                let value = app.history[&gpu_id][app.alerts[idx].metric].back().copied().unwrap_or_default();
+                let entry = format!("{},gpu {},{},value={}", chrono::Local::now().to_rfc3339(), gpu_id, app.alerts[idx].describe(&names), value);
+                if let Some(ref mut file) = alert_log {
+                    writeln!(file, "{}", entry).ok();
+                } else if terminal.is_none() {
+                    eprintln!("{}", entry);
+                }
+                if let Some(ref url) = webhook {
+                    let rule = &app.alerts[idx];
+                    post_webhook(url, serde_json::json!({
+                        "metric": names[rule.metric],
+                        "value": value,
+                        "threshold": rule.threshold,
+                        "gpu": gpu_id.to_json(),
+                        "ts": chrono::Local::now().to_rfc3339(),
+                    }));
+                }
+                if let Some(ref cmd) = args.trigger_cmd {
+                    if last_triggered.get(&idx).is_none_or(|at| at.elapsed() >= TRIGGER_COOLDOWN) {
+                        last_triggered.insert(idx, Instant::now());
+                        run_trigger(cmd, app.alerts[idx].describe(&names), gpu_id, value, terminal.is_some());
+                    }
+                }
+            }
+            if let Some(ref mut terminal) = terminal {
+                let mut layout = Vec::new();
+                app.record_fps();
+                let draw_started = Instant::now();
+                let frame = match terminal.draw(|f| layout = draw(f, &app)) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        failure = Some(AppError::TerminalError(e));
+                        break;
+                    }
+                };
+                app.record_draw(draw_started.elapsed());
+                if let Some(ref mut writer) = recorder {
+                    let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+                    if let Err(e) = writer.write(timestamp_ms, frame.buffer) {
+                        app.flash(format!("Recording stopped: {}", e), true);
+                        recorder = None;
+                    }
+                }
+                if std::mem::take(&mut app.export_requested) {
+                    let path = args.export_png.clone().unwrap_or_else(|| format!("dcgmi_screen_{}.png", chrono::Local::now().format("%Y%m%d_%H%M%S")));
+                    png_exports.push(export_png(frame.buffer.clone(), app.theme.export_colors(), path));
+                }
+                app.last_layout = layout;
+            }
+            app.tick_flash();
+            last_render = Instant::now();
+        }
+
+        let event = if terminal.is_none() {
+            thread::sleep(Duration::from_millis(10));
+            None
+        } else {
+            match next_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    failure = Some(AppError::TerminalError(e));
+                    break;
+                }
+            }
+        };
+        if let Some(event) = event {
+            let key = match event {
+                Event::Key(key) => Some(key),
+                Event::Mouse(mouse) => {
+                    handle_mouse(&mut app, mouse);
+                    None
+                }
+                _ => None,
+            };
+            if let Some(key) = key {
+                if let Some(ref mut idle) = app.idle {
+                    idle.reset();
+                }
+                if handle_key(&mut app, key) {
+                    break;
+                }
+                for text in std::mem::take(&mut app.pending_annotations) {
+                    let written = match (&logger, &log_dir) {
+                        (Some(logger), _) if logger.supports_annotations => {
+                            logger.annotate(text);
+                            Ok(())
+                        }
+                        (_, Some(logs)) if logs.supports_annotations() => {
+                            logs.annotate(&text);
+                            Ok(())
+                        }
+                        _ => append_annotation(&annotation_path, &text, &args.csv_timestamp_format),
+                    };
+                    match written {
+                        Ok(()) => app.flash("Annotated".to_string(), false),
+                        Err(e) => app.flash(format!("Annotation failed: {}", e), true),
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(mut live) = live_source {
+        live.stop();
+    }
+    if let Some(logger) = logger {
+        logger.finish();
+    }
+    if let Some(logs) = log_dir {
+        logs.finish();
+    }
+    if let Some(ring) = ring_logger {
+        ring.finish();
+    }
+    if let Some(ref path) = args.socket {
+        std::fs::remove_file(path).ok();
+    }
+
+    if let Some(ref path) = session {
+        let saved = SessionFile { fields: app.fields.clone(), history: app.history.clone() };
+        save_session(path, &saved).ok();
+    }
+
+    for handle in png_exports {
+        handle.join().ok();
+    }
+    let final_export = args.export_png.as_ref().map(|path| {
+        let buffer = match terminal {
+            Some(ref mut terminal) => terminal.draw(|f| {
+                draw(f, &app);
+            }).map(|frame| frame.buffer.clone()),
+            None => Terminal::new(TestBackend::new(EXPORT_COLUMNS, EXPORT_ROWS))
+                .and_then(|mut offscreen| offscreen.draw(|f| {
+                    draw(f, &app);
+                }).map(|frame| frame.buffer.clone())),
+        };
+        buffer.map_err(|e| e.to_string()).and_then(|buffer| export_png(buffer, app.theme.export_colors(), path.clone()).join().unwrap_or_else(|_| Err("renderer panicked".to_string())))
+    });
+
+    if let Some(mut terminal) = terminal {
+        // Every step is tried even after one fails, and an error from the loop is the one reported
+        let restored = [
+            disable_raw_mode(),
+            execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture),
+            terminal.show_cursor(),
+        ];
+        if let Some(e) = restored.into_iter().find_map(Result::err) {
+            failure.get_or_insert(AppError::TerminalError(e));
+        }
+    }
+    if let (true, Some(pid)) = (watched_exited, args.watch_pid) {
+        println!("PID {} exited after {}; {} samples collected", pid, format_duration(app.started.elapsed()), app.samples_seen);
+        if let Some(ref path) = args.log_file {
+            println!("Log written to {}", path);
+        }
+        if let Some(ref dir) = args.log_dir {
+            println!("Logs written to {}", dir);
+        }
+    }
+    if args.headless_summary {
+        print_summary(&app.summary(), args.summary_format);
+    }
+    if let Some(Err(e)) = final_export {
+        eprintln!("PNG export failed: {}", e);
+    }
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a `dcgmi` or `nvidia-smi` query may take before it is killed
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Picks `| 0 | Name: NVIDIA A100-SXM4-80GB |` rows out of `dcgmi discovery -l`
+pub fn parse_discovery(output: &str) -> BTreeMap<usize, String> {
+    let mut names = BTreeMap::new();
+    for line in output.lines() {
+        let cells: Vec<&str> = line.split('|').map(str::trim).filter(|c| !c.is_empty()).collect();
+        let [id, info] = cells[..] else {
+            continue;
+        };
+        if let (Ok(id), Some(name)) = (id.parse(), info.strip_prefix("Name:")) {
+            names.insert(id, name.trim().to_string());
+        }
+    }
+    names
+}
+
+/// Stdout of `program args`, or why it is missing, failed (with its stderr) or was killed
+/// for outliving `DISCOVERY_TIMEOUT`
+pub(crate) fn run_with_timeout(program: &str, args: &[&str]) -> Result<String, String> {
+    let command = format!("{} {}", program, args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if started.elapsed() < DISCOVERY_TIMEOUT => thread::sleep(Duration::from_millis(50)),
+            _ => {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(format!("{} timed out after {}s", command, DISCOVERY_TIMEOUT.as_secs()));
+            }
+        }
+    }
+    let output = child.wait_with_output().map_err(|e| format!("{} failed: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", command, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Model name per GPU id, or an empty map if dcgmi is missing, fails or times out
+pub fn query_gpu_names() -> BTreeMap<usize, String> {
+    run_with_timeout("dcgmi", &["discovery", "-l"]).ok().map(|output| parse_discovery(&output)).unwrap_or_default()
+}
+
+/// Highest (SM, memory) clock in MHz among the `nvidia-smi` rows `1980, 1593`, one per GPU
+pub fn parse_max_clocks(output: &str) -> Option<(f64, f64)> {
+    let clocks: Vec<(f64, f64)> = output
+        .lines()
+        .filter_map(|line| {
+            let (sm, mem) = line.split_once(',')?;
+            Some((sm.trim().parse().ok()?, mem.trim().parse().ok()?))
+        })
+        .collect();
+    let max = |pick: fn(&(f64, f64)) -> f64| clocks.iter().map(pick).fold(f64::NAN, f64::max);
+    (!clocks.is_empty()).then(|| (max(|c| c.0), max(|c| c.1)))
+}
+
+/// Rated maximum SM and memory clocks from `nvidia-smi`, used as the clock charts' ceiling
+pub fn query_max_clocks() -> Option<(f64, f64)> {
+    parse_max_clocks(&run_with_timeout("nvidia-smi", &["--query-gpu=clocks.max.sm,clocks.max.memory", "--format=csv,noheader,nounits"]).ok()?)
+}
+
+/// Total memory in MB per GPU index from `nvidia-smi` rows `0, 81920`
+pub fn parse_total_memory(output: &str) -> BTreeMap<usize, f64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (index, total) = line.split_once(',')?;
+            Some((index.trim().parse().ok()?, total.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Each GPU's total memory from `nvidia-smi`, or an empty map if it is missing or fails
+pub fn query_total_memory() -> BTreeMap<usize, f64> {
+    run_with_timeout("nvidia-smi", &["--query-gpu=index,memory.total", "--format=csv,noheader,nounits"])
+        .ok()
+        .map(|output| parse_total_memory(&output))
+        .unwrap_or_default()
+}
+
+/// A compute process on one GPU, as listed by `dcgmi pid`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    pub memory_mb: f64,
+}
+
+/// Rows whose first cell is a PID and last a memory size in MB, in either `dcgmi`'s boxed
+/// form `| 12345 | python | 2048 MiB |` or whitespace-separated `12345 python 2048`
+pub fn parse_processes(output: &str) -> Vec<GpuProcess> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let cells: Vec<&str> = if line.contains('|') {
+                line.split('|').map(str::trim).filter(|c| !c.is_empty()).collect()
+            } else {
+                line.split_whitespace().collect()
+            };
+            let (first, rest) = cells.split_first()?;
+            let (last, middle) = rest.split_last()?;
+            let memory = last.trim_end_matches(|c: char| c.is_alphabetic()).trim();
+            // A bare `2048 MiB` splits into two whitespace cells
+            let (memory, middle) = match (memory.is_empty(), middle.split_last()) {
+                (true, Some((size, name))) => (*size, name),
+                _ => (memory, middle),
+            };
+            if middle.is_empty() {
+                return None;
+            }
+            Some(GpuProcess { pid: first.parse().ok()?, name: middle.join(" "), memory_mb: memory.parse().ok()? })
+        })
+        .collect()
+}
+
+/// Processes on GPU `gpu` from `dcgmi pid`, or none if it is missing, fails or times out
+pub fn query_processes(gpu: usize) -> Vec<GpuProcess> {
+    run_with_timeout("dcgmi", &["pid", "--gpu-id", &gpu.to_string()]).ok().map(|output| parse_processes(&output)).unwrap_or_default()
+}
@@ -0,0 +1,291 @@
+//! Parsing, statistics and formatting shared by the `dcgmi_tui` binary, plus the
+//! logging, discovery and topology helpers it is built from.
+
+pub mod discovery;
+pub mod logging;
+pub mod nvml;
+pub mod recording;
+pub mod ringlog;
+pub mod screenshot;
+pub mod topology;
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub type MetricBuffer = VecDeque<f64>;
+
+/// DCGM field ids monitored when `--fields` is not given, with their display names
+pub const DEFAULT_FIELDS: [(u32, &str); 14] = [
+    (1002, "SMACT"), (1003, "SMOCC"), (1004, "TENSO"), (1006, "FP64A"), (1007, "FP32A"), (1008, "FP16A"),
+    (1005, "DRAMA"), (1009, "PCITX"), (1010, "PCIRX"), (1011, "NVLTX"), (1012, "NVLRX"), (252, "FB_USED"),
+    (150, "TEMP"), (155, "POWER"),
+];
+
+/// `(field id, DCGM name, description, unit)` printed by `--field-doc`
+pub const FIELD_DOCS: &[(u32, &str, &str, &str)] = &[
+    (100, "SM_CLOCK", "SM clock", "MHz"),
+    (101, "MEM_CLOCK", "Memory clock", "MHz"),
+    (150, "GPU_TEMP", "GPU temperature", "°C"),
+    (155, "POWER_USAGE", "Power draw", "W"),
+    (250, "FB_TOTAL", "Total frame buffer memory", "MB"),
+    (251, "FB_FREE", "Free frame buffer memory", "MB"),
+    (252, "FB_USED", "Frame buffer memory in use", "MB"),
+    (253, "FB_RESERVED", "Frame buffer memory reserved by the driver", "MB"),
+    (1001, "GR_ENGINE_ACTIVE", "Ratio of time the graphics engine was active", "ratio"),
+    (1002, "SM_ACTIVE", "Ratio of cycles an SM had at least one warp assigned", "ratio"),
+    (1003, "SM_OCCUPANCY", "Resident warps as a ratio of the SM's maximum", "ratio"),
+    (1004, "PIPE_TENSOR_ACTIVE", "Ratio of cycles the tensor (HMMA) pipe was active", "ratio"),
+    (1005, "DRAM_ACTIVE", "Ratio of cycles the device memory interface was sending or receiving", "ratio"),
+    (1006, "PIPE_FP64_ACTIVE", "Ratio of cycles the FP64 pipe was active", "ratio"),
+    (1007, "PIPE_FP32_ACTIVE", "Ratio of cycles the FP32 pipe was active", "ratio"),
+    (1008, "PIPE_FP16_ACTIVE", "Ratio of cycles the FP16 pipe was active", "ratio"),
+    (1009, "PCIE_TX_BYTES", "PCIe bytes transmitted", "B/s"),
+    (1010, "PCIE_RX_BYTES", "PCIe bytes received", "B/s"),
+    (1011, "NVLINK_TX_BYTES", "NVLink bytes transmitted, all links", "B/s"),
+    (1012, "NVLINK_RX_BYTES", "NVLink bytes received, all links", "B/s"),
+];
+
+/// SM and memory clock fields added by `--gpu-clock`
+pub const CLOCK_FIELDS: [(u32, &str); 2] = [(100, "SMCLK"), (101, "MEMCLK")];
+
+/// How a field's raw values are formatted in the stats panel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricKind {
+    /// 0..1 activity ratio, shown as a percentage
+    Ratio,
+    /// Bytes per second
+    BytesPerSec,
+    /// Framebuffer sizes, reported by DCGM in MB
+    Megabytes,
+    /// Degrees Celsius
+    Celsius,
+    /// Watts
+    Watts,
+    /// Clock speeds in MHz
+    Megahertz,
+    Plain,
+}
+
+/// Unit of a DCGM field, for the ids this tool knows about
+pub fn metric_kind(field_id: u32) -> MetricKind {
+    match field_id {
+        1001..=1008 => MetricKind::Ratio,
+        1009..=1012 => MetricKind::BytesPerSec,
+        250..=253 => MetricKind::Megabytes,
+        150 => MetricKind::Celsius,
+        155 => MetricKind::Watts,
+        100 | 101 => MetricKind::Megahertz,
+        _ => MetricKind::Plain,
+    }
+}
+
+/// Formats a byte count with a binary (1024-based) unit from B up to TB
+pub fn format_bytes_with_unit(value: f64, per_sec: bool) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    const GB: f64 = MB * 1024.0;
+    const TB: f64 = GB * 1024.0;
+
+    let (num, unit) = if value >= TB {
+        (value / TB, "TB")
+    } else if value >= GB {
+        (value / GB, "GB")
+    } else if value >= MB {
+        (value / MB, "MB")
+    } else if value >= KB {
+        (value / KB, "KB")
+    } else {
+        (value, "B")
+    };
+
+    if unit == "B" {
+        if per_sec {
+            format!("{:.0} B/s", num)
+        } else {
+            format!("{:.0} B", num)
+        }
+    } else {
+        if per_sec {
+            format!("{:.2} {}/s", num, unit)
+        } else {
+            format!("{:.2} {}", num, unit)
+        }
+    }
+}
+
+/// Pearson r of the paired samples of `a` and `b`; NaN when either series is constant
+/// or there are fewer than two pairs
+pub fn pearson(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len().min(b.len());
+    if n < 2 {
+        return f64::NAN;
+    }
+    let mean = |x: &[f64]| x[..n].iter().sum::<f64>() / n as f64;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a[..n].iter().zip(&b[..n]) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        cov += dx * dy;
+        var_a += dx * dx;
+        var_b += dy * dy;
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return f64::NAN;
+    }
+    (cov / (var_a * var_b).sqrt()).clamp(-1.0, 1.0)
+}
+
+/// Linearly interpolated percentile (0..=100) of an ascending slice; 0 when empty
+pub fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (pct / 100.0) * (sorted.len() - 1) as f64;
+    let low = rank.floor() as usize;
+    let high = rank.ceil() as usize;
+    if low == high {
+        sorted[low]
+    } else {
+        let weight = rank - low as f64;
+        sorted[low] * (1.0 - weight) + sorted[high] * weight
+    }
+}
+
+/// The requested percentiles of the positive samples in a buffer; idle (zero) samples are ignored
+pub fn buffer_percentiles(buf: &MetricBuffer, pcts: &[f64]) -> Vec<f64> {
+    let mut sorted: Vec<f64> = buf.iter().copied().filter(|v| *v > 0.0).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    pcts.iter().map(|&pct| percentile(&sorted, pct)).collect()
+}
+
+pub fn parse_percentile(s: &str) -> Result<f64, String> {
+    let pct: f64 = s.trim().parse().map_err(|_| format!("invalid percentile `{}`", s))?;
+    if (0.0..=100.0).contains(&pct) {
+        Ok(pct)
+    } else {
+        Err(format!("percentile `{}` must be between 0 and 100", s))
+    }
+}
+
+/// Formats a raw sample for the stats panel according to its field's kind
+pub fn format_metric_value(kind: MetricKind, value: f64) -> String {
+    match kind {
+        MetricKind::BytesPerSec => format_bytes_with_unit(value, true),
+        // By default MB
+        MetricKind::Megabytes => format_bytes_with_unit(value * 1024.0 * 1024.0, false),
+        MetricKind::Ratio => format!("{:.1}%", value * 100.0),
+        MetricKind::Celsius => format!("{:.0}°C", value),
+        MetricKind::Watts => format!("{:.2} W", value),
+        MetricKind::Megahertz => format!("{:.0} MHz", value),
+        MetricKind::Plain => format!("{:.1}", value),
+    }
+}
+
+/// Like `format_metric_value` but at most one decimal and no spaces, for `--watchface`
+pub fn format_compact_value(kind: MetricKind, value: f64) -> String {
+    let bytes = |value: f64, suffix: &str| {
+        let units = ["B", "KB", "MB", "GB", "TB"];
+        let mut num = value;
+        let mut unit = 0;
+        while num >= 1024.0 && unit < units.len() - 1 {
+            num /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 { format!("{:.0}B{}", num, suffix) } else { format!("{:.1}{}{}", num, units[unit], suffix) }
+    };
+    match kind {
+        MetricKind::BytesPerSec => bytes(value, "/s"),
+        MetricKind::Megabytes => bytes(value * 1024.0 * 1024.0, ""),
+        MetricKind::Ratio => format!("{:.0}%", value * 100.0),
+        MetricKind::Celsius => format!("{:.0}°C", value),
+        MetricKind::Watts => format!("{:.0}W", value),
+        MetricKind::Megahertz => format!("{:.0}MHz", value),
+        MetricKind::Plain => format!("{:.1}", value),
+    }
+}
+
+/// Short `--watchface` label of a metric. Well-known fields get fixed abbreviations unless
+/// `--field-alias` renamed them; `combined` marks a TX field summed with its RX partner.
+pub fn watchface_label(field_id: u32, name: &str, combined: bool, aliased: bool) -> String {
+    if aliased {
+        return name.to_string();
+    }
+    let label = match (field_id, combined) {
+        (1011, true) => "NVL⇅",
+        (1009, true) => "PCIe⇅",
+        (1002, _) => "SM",
+        (1003, _) => "OCC",
+        (1004, _) => "TC",
+        (1005, _) => "MEM",
+        (1006, _) => "FP64",
+        (1007, _) => "FP32",
+        (1008, _) => "FP16",
+        (1009, _) => "PCIe↑",
+        (1010, _) => "PCIe↓",
+        (1011, _) => "NVL↑",
+        (1012, _) => "NVL↓",
+        (252, _) => "FB",
+        (150, _) => "TEMP",
+        (155, _) => "PWR",
+        _ => name,
+    };
+    label.to_string()
+}
+
+/// A monitored GPU, or one MIG instance of it (`--mig`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Entity {
+    pub gpu: usize,
+    /// MIG instance id, `None` for the whole GPU
+    pub instance: Option<usize>,
+}
+
+impl Entity {
+    pub fn gpu(gpu: usize) -> Self {
+        Entity { gpu, instance: None }
+    }
+
+    /// JSON form used by the JSONL log and --alert-webhook: whole GPUs stay numeric,
+    /// MIG instances need the `gpu/instance` string
+    pub fn to_json(&self) -> serde_json::Value {
+        match self.instance {
+            Some(_) => self.to_string().into(),
+            None => self.gpu.into(),
+        }
+    }
+}
+
+/// `3` for a whole GPU, `3/1` for MIG instance 1 of GPU 3, as dcgmi prints them
+impl std::fmt::Display for Entity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.instance {
+            Some(instance) => write!(f, "{}/{}", self.gpu, instance),
+            None => write!(f, "{}", self.gpu),
+        }
+    }
+}
+
+impl std::str::FromStr for Entity {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((gpu, instance)) => Ok(Entity { gpu: gpu.parse()?, instance: Some(instance.parse()?) }),
+            None => Ok(Entity::gpu(s.parse()?)),
+        }
+    }
+}
+
+/// Parses a `dcgmi dmon` data line such as `GPU 3  0.12 0.40 ...` (or `GPU 3/1 ...`
+/// for a MIG instance) into the entity and its metric values. Lines without exactly
+/// `field_count` values are rejected.
+pub fn parse_metric_line(line: &str, field_count: usize) -> Option<(Entity, Vec<f64>)> {
+    let mut parts = line.strip_prefix("GPU")?.split_whitespace();
+    let gpu_id: Entity = parts.next()?.parse().ok()?;
+    let values: Vec<f64> = parts.filter_map(|s| s.parse().ok()).collect();
+    if values.len() == field_count {
+        Some((gpu_id, values))
+    } else {
+        None
+    }
+}
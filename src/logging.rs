@@ -0,0 +1,552 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use parquet::basic::{Repetition, Type as PhysicalType};
+use parquet::data_type::{DoubleType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ringlog::RingWriter;
+use crate::{percentile, Entity};
+
+/// Statistics written per metric by --aggregate-period, in column order
+pub const AGGREGATE_STATS: [&str; 6] = ["mean", "p50", "p90", "p99", "min", "max"];
+
+/// Row format written by the logger thread
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Csv,
+    /// One JSON object per line keyed by metric name, plus `ts` and `gpu`
+    Jsonl,
+    /// Columnar file with `timestamp` (Unix ms), `gpu`, `mig` (-1 for a whole GPU) and
+    /// one FLOAT64 column per metric
+    Parquet,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// How the CSV/JSONL `timestamp`/`ts` column is written
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimestampFormat {
+    UnixMs,
+    /// Seconds with millisecond precision
+    UnixS,
+    Rfc3339,
+    /// Any other `strftime` pattern, validated by `parse`
+    Strftime(String),
+}
+
+impl TimestampFormat {
+    /// Accepts `unix_ms`, `unix_s`, `rfc3339` or a `strftime` pattern such as `%Y-%m-%d %H:%M:%S%.3f`
+    pub fn parse(s: &str) -> Result<TimestampFormat, String> {
+        match s {
+            "unix_ms" => Ok(TimestampFormat::UnixMs),
+            "unix_s" => Ok(TimestampFormat::UnixS),
+            "rfc3339" => Ok(TimestampFormat::Rfc3339),
+            _ if !s.contains('%') => Err(format!("unknown timestamp format `{}`; expected unix_ms, unix_s, rfc3339 or a strftime pattern", s)),
+            _ if StrftimeItems::new(s).any(|item| item == Item::Error) => Err(format!("invalid strftime pattern `{}`", s)),
+            _ => Ok(TimestampFormat::Strftime(s.to_string())),
+        }
+    }
+
+    pub fn format(&self, time: DateTime<Local>) -> String {
+        match self {
+            TimestampFormat::UnixMs => time.timestamp_millis().to_string(),
+            TimestampFormat::UnixS => format!("{:.3}", time.timestamp_millis() as f64 / 1000.0),
+            TimestampFormat::Rfc3339 => time.to_rfc3339(),
+            TimestampFormat::Strftime(pattern) => time.format(pattern).to_string(),
+        }
+    }
+
+    /// Unix timestamps stay numbers in JSON; everything else is a string
+    fn json(&self, time: DateTime<Local>) -> serde_json::Value {
+        match self {
+            TimestampFormat::UnixMs => time.timestamp_millis().into(),
+            TimestampFormat::UnixS => (time.timestamp_millis() as f64 / 1000.0).into(),
+            _ => self.format(time).into(),
+        }
+    }
+}
+
+/// What the logger thread is asked to write
+enum Record {
+    Sample(Entity, Vec<f64>),
+    /// A label typed with `a`, written as its own row
+    Annotation(String),
+}
+
+/// Handle to the logger thread; `finish` waits for queued rows to reach the disk
+pub struct Logger {
+    tx: Sender<Record>,
+    handle: thread::JoinHandle<()>,
+    /// Parquet has no room for free text, so annotations go to a companion file instead
+    pub supports_annotations: bool,
+}
+
+impl Logger {
+    pub fn send(&self, sample: (Entity, Vec<f64>)) {
+        self.tx.send(Record::Sample(sample.0, sample.1)).ok();
+    }
+
+    pub fn annotate(&self, text: String) {
+        self.tx.send(Record::Annotation(text)).ok();
+    }
+
+    /// Closes the channel so the thread drains it, flushes and closes the file
+    pub fn finish(self) {
+        drop(self.tx);
+        self.handle.join().ok();
+    }
+}
+
+/// Opens the log for writing. With `append`, existing rows are kept and the CSV
+/// header is only wanted if the file is empty; returns whether it is.
+pub fn open_log_file(path: &str, append: bool) -> std::io::Result<(File, bool)> {
+    if !append {
+        return Ok((File::create(path)?, true));
+    }
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let empty = !file.metadata().map(|m| m.len() > 0)?;
+    Ok((file, empty))
+}
+
+/// Buffers samples and writes them to a Parquet file one row group at a time
+pub struct ParquetWriter {
+    writer: SerializedFileWriter<File>,
+    row_group_size: usize,
+    timestamps: Vec<i64>,
+    gpus: Vec<i32>,
+    instances: Vec<i32>,
+    columns: Vec<Vec<f64>>,
+}
+
+impl ParquetWriter {
+    pub fn new(file: File, names: &[String], row_group_size: usize) -> parquet::errors::Result<Self> {
+        let column = |name: &str, physical: PhysicalType| {
+            Type::primitive_type_builder(name, physical).with_repetition(Repetition::REQUIRED).build().map(Arc::new)
+        };
+        let mut fields = vec![
+            column("timestamp", PhysicalType::INT64)?,
+            column("gpu", PhysicalType::INT32)?,
+            column("mig", PhysicalType::INT32)?,
+        ];
+        for name in names {
+            fields.push(column(name, PhysicalType::DOUBLE)?);
+        }
+        let schema = Type::group_type_builder("dcgmi").with_fields(fields).build()?;
+        let writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(WriterProperties::builder().build()))?;
+        Ok(ParquetWriter {
+            writer,
+            row_group_size: row_group_size.max(1),
+            timestamps: Vec::new(),
+            gpus: Vec::new(),
+            instances: Vec::new(),
+            columns: vec![Vec::new(); names.len()],
+        })
+    }
+
+    pub fn write(&mut self, timestamp_ms: i64, entity: Entity, values: &[f64]) -> parquet::errors::Result<()> {
+        self.timestamps.push(timestamp_ms);
+        self.gpus.push(entity.gpu as i32);
+        self.instances.push(entity.instance.map_or(-1, |n| n as i32));
+        for (column, value) in self.columns.iter_mut().zip(values) {
+            column.push(*value);
+        }
+        if self.timestamps.len() >= self.row_group_size {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn flush_row_group(&mut self) -> parquet::errors::Result<()> {
+        let mut row_group = self.writer.next_row_group()?;
+        if let Some(mut col) = row_group.next_column()? {
+            col.typed::<Int64Type>().write_batch(&self.timestamps, None, None)?;
+            col.close()?;
+        }
+        for ids in [&self.gpus, &self.instances] {
+            if let Some(mut col) = row_group.next_column()? {
+                col.typed::<Int32Type>().write_batch(ids, None, None)?;
+                col.close()?;
+            }
+        }
+        for values in &self.columns {
+            if let Some(mut col) = row_group.next_column()? {
+                col.typed::<DoubleType>().write_batch(values, None, None)?;
+                col.close()?;
+            }
+        }
+        row_group.close()?;
+        self.timestamps.clear();
+        self.gpus.clear();
+        self.instances.clear();
+        self.columns.iter_mut().for_each(Vec::clear);
+        Ok(())
+    }
+
+    /// Writes any buffered rows and the footer; without this the file is unreadable
+    pub fn close(mut self) -> parquet::errors::Result<()> {
+        if !self.timestamps.is_empty() {
+            self.flush_row_group()?;
+        }
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Size-based rotation of a text log: once it reaches `max_bytes` it is renamed to
+/// `<stem>.1.<ext>`, older rotations shift up by one and only `keep` of them are kept
+#[derive(Clone)]
+pub struct Rotation {
+    pub path: String,
+    pub max_bytes: u64,
+    pub keep: usize,
+}
+
+impl Rotation {
+    /// `out.csv` becomes `out.<n>.csv`; a path without an extension gets `.<n>`
+    pub fn rotated_path(&self, n: usize) -> String {
+        let path = Path::new(&self.path);
+        let rotated = match path.extension() {
+            Some(ext) => path.with_extension(format!("{}.{}", n, ext.to_string_lossy())),
+            None => path.with_extension(n.to_string()),
+        };
+        rotated.to_string_lossy().into_owned()
+    }
+
+    /// Shifts the rotated files up, moves the live log to `.1` and creates a fresh one
+    fn rotate(&self) -> std::io::Result<File> {
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            std::fs::remove_file(self.rotated_path(self.keep)).ok();
+            for n in (1..self.keep).rev() {
+                std::fs::rename(self.rotated_path(n), self.rotated_path(n + 1)).ok();
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        File::create(&self.path)
+    }
+}
+
+/// How the logger thread writes its file
+#[derive(Clone)]
+pub struct LogOptions {
+    pub format: OutputFormat,
+    /// Timestamp column of the text formats
+    pub timestamp: TimestampFormat,
+    /// Experiment name put in a `label` column/key of the text formats
+    pub label: Option<String>,
+    /// Run identifier put in a `session_id` column/key of the text formats
+    pub session_id: Option<String>,
+    /// Separator between CSV cells, from `parse_delimiter`
+    pub delimiter: char,
+    /// Whether CSV files start with a header row, including ones opened by rotation
+    pub header: bool,
+    /// Samples per Parquet row group
+    pub row_group_size: usize,
+    /// Text formats only; Parquet cannot be split mid-file
+    pub rotation: Option<Rotation>,
+    /// CSV only: write one row of statistics per GPU for each window this long instead of
+    /// every sample
+    pub aggregate_period: Option<Duration>,
+}
+
+/// Spawns the thread that writes every sample sent to the returned `Logger`.
+/// `write_header` says whether `file` still needs its CSV header (it may be appended to).
+pub fn spawn_logger_thread(file: File, names: Vec<String>, write_header: bool, options: LogOptions) -> std::io::Result<Logger> {
+    let (tx, rx) = mpsc::channel::<Record>();
+    if options.format == OutputFormat::Parquet {
+        let mut writer = ParquetWriter::new(file, &names, options.row_group_size).map_err(std::io::Error::other)?;
+        let handle = thread::spawn(move || {
+            while let Ok(record) = rx.recv() {
+                if let Record::Sample(gpu_id, values) = record {
+                    writer.write(Local::now().timestamp_millis(), gpu_id, &values).ok();
+                }
+            }
+            writer.close().ok();
+        });
+        return Ok(Logger { tx, handle, supports_annotations: false });
+    }
+    if let Some(period) = options.aggregate_period {
+        return Ok(spawn_aggregate_thread(file, names, write_header, period, options));
+    }
+
+    let handle = thread::spawn(move || {
+        let LogOptions { format, timestamp, label, session_id, delimiter, header, rotation, .. } = options;
+        let sep = delimiter.to_string();
+        let mut size = file.metadata().map_or(0, |m| m.len());
+        let mut file = BufWriter::new(file);
+        // The label and session columns sit just before the annotation so metric columns keep their place
+        let extra_cells: String = [&label, &session_id].iter().filter_map(|cell| cell.as_deref()).map(|cell| format!("{}{}", csv_quote(cell, delimiter), sep)).collect();
+        let extra_columns: String = [("label", &label), ("session_id", &session_id)].iter().filter(|(_, cell)| cell.is_some()).map(|(column, _)| format!("{}{}", column, sep)).collect();
+        // Aliases may hold the delimiter, so metric names are quoted like the cells below them
+        let quoted: Vec<String> = names.iter().map(|name| csv_quote(name, delimiter)).collect();
+        let header = (format == OutputFormat::Csv && header)
+            .then(|| format!("timestamp{sep}gpu{sep}{}{sep}{}annotation", quoted.join(&sep), extra_columns));
+        if let (true, Some(ref header)) = (write_header, &header) {
+            writeln!(file, "{}", header).ok();
+            size += header.len() as u64 + 1;
+        }
+        while let Ok(record) = rx.recv() {
+            let now = Local::now();
+            let line = match (record, format) {
+                (Record::Annotation(text), OutputFormat::Csv) => {
+                    format!("{}{sep}{sep}{}{sep}{}{}", csv_quote(&timestamp.format(now), delimiter), vec!["NaN"; names.len()].join(&sep), extra_cells, csv_quote(&text, delimiter))
+                }
+                (Record::Annotation(text), _) => {
+                    let mut row = serde_json::json!({ "ts": timestamp.json(now), "annotation": text });
+                    if let Some(ref label) = label {
+                        row["label"] = label.clone().into();
+                    }
+                    if let Some(ref session_id) = session_id {
+                        row["session_id"] = session_id.clone().into();
+                    }
+                    row.to_string()
+                }
+                // The trailing delimiter leaves the annotation column empty
+                (Record::Sample(gpu_id, values), OutputFormat::Csv) => {
+                    let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(&sep);
+                    format!("{}{sep}{}{sep}{}{sep}{}", csv_quote(&timestamp.format(now), delimiter), gpu_id, values, extra_cells)
+                }
+                (Record::Sample(gpu_id, values), _) => {
+                    let mut row = serde_json::Map::new();
+                    row.insert("ts".to_string(), timestamp.json(now));
+                    row.insert("gpu".to_string(), gpu_id.to_json());
+                    if let Some(ref label) = label {
+                        row.insert("label".to_string(), label.clone().into());
+                    }
+                    if let Some(ref session_id) = session_id {
+                        row.insert("session_id".to_string(), session_id.clone().into());
+                    }
+                    for (name, value) in names.iter().zip(&values) {
+                        row.insert(name.clone(), (*value).into());
+                    }
+                    serde_json::Value::Object(row).to_string()
+                }
+            };
+            writeln!(file, "{}", line).ok();
+            // Flush every row so `tail -f` sees samples as they arrive
+            file.flush().ok();
+            size += line.len() as u64 + 1;
+            let Some(ref rotation) = rotation else {
+                continue;
+            };
+            if size < rotation.max_bytes {
+                continue;
+            }
+            // On failure the current file simply keeps growing
+            if let Ok(fresh) = rotation.rotate() {
+                file = BufWriter::new(fresh);
+                size = 0;
+                if let Some(ref header) = header {
+                    writeln!(file, "{}", header).ok();
+                    file.flush().ok();
+                    size = header.len() as u64 + 1;
+                }
+            }
+        }
+    });
+    Ok(Logger { tx, handle, supports_annotations: true })
+}
+
+/// Rolls every `period` of samples up into one CSV row per GPU: `window_start`,
+/// `window_end`, `gpu`, `samples`, then `<metric>_<stat>` for each of `AGGREGATE_STATS`.
+/// The last, partial window is written when the logger finishes. Annotations have no
+/// column here, so the caller gets `supports_annotations: false`.
+fn spawn_aggregate_thread(file: File, names: Vec<String>, write_header: bool, period: Duration, options: LogOptions) -> Logger {
+    let (tx, rx) = mpsc::channel::<Record>();
+    let handle = thread::spawn(move || {
+        let LogOptions { timestamp, label, session_id, delimiter, header, .. } = options;
+        let sep = delimiter.to_string();
+        let mut file = BufWriter::new(file);
+        let extra_cells: String = [&label, &session_id].iter().filter_map(|cell| cell.as_deref()).map(|cell| format!("{}{}", sep, csv_quote(cell, delimiter))).collect();
+        if write_header && header {
+            let stats: Vec<String> = names.iter().flat_map(|name| AGGREGATE_STATS.iter().map(move |stat| csv_quote(&format!("{}_{}", name, stat), delimiter))).collect();
+            let extra_columns: String = [("label", &label), ("session_id", &session_id)].iter().filter(|(_, cell)| cell.is_some()).map(|(column, _)| format!("{}{}", sep, column)).collect();
+            writeln!(file, "window_start{sep}window_end{sep}gpu{sep}samples{sep}{}{}", stats.join(&sep), extra_columns).ok();
+            file.flush().ok();
+        }
+        // Raw samples of the open window, per GPU and then per metric
+        let mut window: BTreeMap<Entity, Vec<Vec<f64>>> = BTreeMap::new();
+        let mut start = Local::now();
+        let mut deadline = Instant::now() + period;
+        let mut flush = |window: &mut BTreeMap<Entity, Vec<Vec<f64>>>, start: DateTime<Local>, end: DateTime<Local>| {
+            let (start, end) = (csv_quote(&timestamp.format(start), delimiter), csv_quote(&timestamp.format(end), delimiter));
+            for (gpu_id, columns) in std::mem::take(window) {
+                let samples = columns.first().map_or(0, Vec::len);
+                let mut cells = Vec::with_capacity(columns.len() * AGGREGATE_STATS.len());
+                for mut values in columns {
+                    values.sort_by(f64::total_cmp);
+                    let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+                    let stats = [mean, percentile(&values, 50.0), percentile(&values, 90.0), percentile(&values, 99.0), values[0], values[values.len() - 1]];
+                    cells.extend(stats.iter().map(f64::to_string));
+                }
+                writeln!(file, "{}{sep}{}{sep}{}{sep}{}{sep}{}{}", start, end, gpu_id, samples, cells.join(&sep), extra_cells).ok();
+            }
+            file.flush().ok();
+        };
+        loop {
+            // Checked before receiving so a steady stream of samples cannot hold a window open
+            if Instant::now() >= deadline {
+                let end = Local::now();
+                flush(&mut window, start, end);
+                start = end;
+                deadline += period;
+                continue;
+            }
+            match rx.recv_timeout(deadline - Instant::now()) {
+                Ok(Record::Sample(gpu_id, values)) => {
+                    let columns = window.entry(gpu_id).or_insert_with(|| vec![Vec::new(); values.len()]);
+                    for (column, value) in columns.iter_mut().zip(values) {
+                        column.push(value);
+                    }
+                }
+                Ok(Record::Annotation(_)) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    flush(&mut window, start, Local::now());
+                    break;
+                }
+            }
+        }
+    });
+    Logger { tx, handle, supports_annotations: false }
+}
+
+/// Spawns a logger that writes samples into a fixed-size --samplelog-bin ring file
+pub fn spawn_ring_logger_thread(mut writer: RingWriter) -> Logger {
+    let (tx, rx) = mpsc::channel::<Record>();
+    let handle = thread::spawn(move || {
+        while let Ok(record) = rx.recv() {
+            if let Record::Sample(gpu_id, values) = record {
+                writer.write(Local::now().timestamp_millis(), gpu_id, &values).ok();
+            }
+        }
+    });
+    Logger { tx, handle, supports_annotations: false }
+}
+
+/// Accepts `comma`, `tab`, `semicolon` or any single character that cannot occur in a
+/// number, a whole or MIG GPU id (`0/1`) or a built-in timestamp format
+pub fn parse_delimiter(s: &str) -> Result<char, String> {
+    let delimiter = match s {
+        "comma" => ',',
+        "tab" => '\t',
+        "semicolon" => ';',
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("unknown delimiter `{}`; expected comma, tab, semicolon or a single character", s)),
+            }
+        }
+    };
+    if delimiter.is_alphanumeric() || ".-+:/\"\r\n".contains(delimiter) {
+        return Err(format!("`{}` can appear in values or timestamps and cannot be the delimiter", delimiter.escape_default()));
+    }
+    Ok(delimiter)
+}
+
+/// `--log-dir`: one file and logger thread per GPU, `gpu_<n>_<session>.<ext>`, each opened
+/// when that GPU's first sample arrives so GPUs discovered mid-run get theirs too.
+/// MIG instances share their parent GPU's file.
+pub struct LogDir {
+    dir: PathBuf,
+    session: String,
+    names: Vec<String>,
+    options: LogOptions,
+    loggers: BTreeMap<usize, Logger>,
+}
+
+impl LogDir {
+    /// Creates `dir` if needed; `session` tells this run's files apart from earlier ones
+    pub fn create(dir: &str, session: String, names: Vec<String>, options: LogOptions) -> std::io::Result<LogDir> {
+        std::fs::create_dir_all(dir)?;
+        Ok(LogDir { dir: PathBuf::from(dir), session, names, options, loggers: BTreeMap::new() })
+    }
+
+    pub fn path(&self, gpu: usize) -> PathBuf {
+        self.dir.join(format!("gpu_{}_{}.{}", gpu, self.session, self.options.format.extension()))
+    }
+
+    pub fn send(&mut self, sample: (Entity, Vec<f64>)) -> std::io::Result<()> {
+        let gpu = sample.0.gpu;
+        if !self.loggers.contains_key(&gpu) {
+            let file = File::create(self.path(gpu))?;
+            let logger = spawn_logger_thread(file, self.names.clone(), self.options.header, self.options.clone())?;
+            self.loggers.insert(gpu, logger);
+        }
+        self.loggers[&gpu].send(sample);
+        Ok(())
+    }
+
+    /// False for Parquet and --aggregate-period, whose files have nowhere to put a note
+    pub fn supports_annotations(&self) -> bool {
+        self.options.format != OutputFormat::Parquet && self.options.aggregate_period.is_none()
+    }
+
+    /// Writes the annotation into every GPU's file opened so far
+    pub fn annotate(&self, text: &str) {
+        for logger in self.loggers.values() {
+            logger.annotate(text.to_string());
+        }
+    }
+
+    pub fn finish(self) {
+        for logger in self.loggers.into_values() {
+            logger.finish();
+        }
+    }
+}
+
+/// Quotes a CSV field when it contains the delimiter, a quote or a newline
+fn csv_quote(text: &str, delimiter: char) -> String {
+    if text.contains([delimiter, '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Splits a CSV row into fields, undoing `csv_quote`
+pub fn split_csv_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Appends `timestamp<TAB>text` to a plain-text annotations file
+pub fn append_annotation(path: &str, text: &str, timestamp: &TimestampFormat) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}\t{}", timestamp.format(Local::now()), text)
+}
@@ -0,0 +1,46 @@
+use nvml_wrapper::enum_wrappers::device::{Clock, PcieUtilCounter, TemperatureSensor};
+use nvml_wrapper::Nvml;
+
+/// DCGM field ids NVML has a close equivalent for. SMACT and DRAMA come from NVML's coarser
+/// kernel-busy and memory-busy percentages, so they read higher than DCGM's ratios.
+pub const NVML_FIELDS: &[u32] = &[1002, 1005, 1009, 1010, 252, 150, 155, 100, 101];
+
+/// A loaded NVML library, read in place of `dcgmi dmon` when DCGM is not installed
+pub struct NvmlReader {
+    nvml: Nvml,
+}
+
+impl NvmlReader {
+    /// Loads `libnvidia-ml`, failing with NVML's reason if the driver or library is missing
+    pub fn init() -> Result<NvmlReader, String> {
+        Nvml::init().map(|nvml| NvmlReader { nvml }).map_err(|e| e.to_string())
+    }
+
+    /// One value per entry of `field_ids` for GPU `index`, in the units dcgmi reports
+    /// (ratios, bytes/s, MB, °C, W, MHz); ids outside `NVML_FIELDS` read as 0
+    pub fn sample(&self, index: u32, field_ids: &[u32]) -> Result<Vec<f64>, String> {
+        let device = self.nvml.device_by_index(index).map_err(|e| format!("GPU {}: {}", index, e))?;
+        let utilization = device.utilization_rates().map_err(|e| e.to_string())?;
+        field_ids
+            .iter()
+            .map(|id| {
+                let value = match id {
+                    1002 => utilization.gpu as f64 / 100.0,
+                    1005 => utilization.memory as f64 / 100.0,
+                    // KB/s
+                    1009 => device.pcie_throughput(PcieUtilCounter::Send)? as f64 * 1024.0,
+                    1010 => device.pcie_throughput(PcieUtilCounter::Receive)? as f64 * 1024.0,
+                    252 => device.memory_info()?.used as f64 / (1024.0 * 1024.0),
+                    150 => device.temperature(TemperatureSensor::Gpu)? as f64,
+                    // mW
+                    155 => device.power_usage()? as f64 / 1000.0,
+                    100 => device.clock_info(Clock::SM)? as f64,
+                    101 => device.clock_info(Clock::Memory)? as f64,
+                    _ => 0.0,
+                };
+                Ok(value)
+            })
+            .collect::<Result<_, nvml_wrapper::error::NvmlError>>()
+            .map_err(|e| format!("GPU {}: {}", index, e))
+    }
+}
@@ -0,0 +1,126 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+
+/// First bytes of every frame in a --record file
+pub const FRAME_MAGIC: &[u8; 4] = b"DTFR";
+/// Fixed part of a frame before its cells
+const FRAME_HEADER: usize = 16;
+/// `char: u32, fg: [u8; 4], bg: [u8; 4], modifier: u16`
+const CELL_SIZE: usize = 14;
+
+/// Colours as `[tag, a, b, c]`: 0 is Reset, 1..=16 the named colours in ANSI order,
+/// 17 `Indexed(a)` and 18 `Rgb(a, b, c)`
+fn encode_color(color: Color) -> [u8; 4] {
+    let tag = match color {
+        Color::Reset => 0,
+        Color::Black => 1,
+        Color::Red => 2,
+        Color::Green => 3,
+        Color::Yellow => 4,
+        Color::Blue => 5,
+        Color::Magenta => 6,
+        Color::Cyan => 7,
+        Color::Gray => 8,
+        Color::DarkGray => 9,
+        Color::LightRed => 10,
+        Color::LightGreen => 11,
+        Color::LightYellow => 12,
+        Color::LightBlue => 13,
+        Color::LightMagenta => 14,
+        Color::LightCyan => 15,
+        Color::White => 16,
+        Color::Indexed(n) => return [17, n, 0, 0],
+        Color::Rgb(r, g, b) => return [18, r, g, b],
+    };
+    [tag, 0, 0, 0]
+}
+
+fn decode_color(bytes: &[u8]) -> Color {
+    const NAMED: [Color; 16] = [
+        Color::Black, Color::Red, Color::Green, Color::Yellow, Color::Blue, Color::Magenta, Color::Cyan, Color::Gray,
+        Color::DarkGray, Color::LightRed, Color::LightGreen, Color::LightYellow, Color::LightBlue, Color::LightMagenta,
+        Color::LightCyan, Color::White,
+    ];
+    match bytes[0] {
+        tag @ 1..=16 => NAMED[tag as usize - 1],
+        17 => Color::Indexed(bytes[1]),
+        18 => Color::Rgb(bytes[1], bytes[2], bytes[3]),
+        _ => Color::Reset,
+    }
+}
+
+/// Appends every drawn frame to a file: `magic: [u8; 4], timestamp_ms: u64, width: u16,
+/// height: u16`, then `width * height` cells row by row, all little-endian. Only the
+/// first character of each cell is kept, as in PNG exports.
+pub struct FrameWriter {
+    file: BufWriter<File>,
+}
+
+impl FrameWriter {
+    pub fn create(path: &str) -> std::io::Result<FrameWriter> {
+        Ok(FrameWriter { file: BufWriter::new(File::create(path)?) })
+    }
+
+    pub fn write(&mut self, timestamp_ms: u64, buffer: &Buffer) -> std::io::Result<()> {
+        let area = buffer.area;
+        let mut frame = Vec::with_capacity(FRAME_HEADER + area.area() as usize * CELL_SIZE);
+        frame.extend_from_slice(FRAME_MAGIC);
+        frame.extend_from_slice(&timestamp_ms.to_le_bytes());
+        frame.extend_from_slice(&area.width.to_le_bytes());
+        frame.extend_from_slice(&area.height.to_le_bytes());
+        for cell in &buffer.content {
+            // The trailing half of a wide character has an empty symbol, stored as 0
+            let c = cell.symbol().chars().next().map_or(0, u32::from);
+            frame.extend_from_slice(&c.to_le_bytes());
+            frame.extend_from_slice(&encode_color(cell.fg));
+            frame.extend_from_slice(&encode_color(cell.bg));
+            frame.extend_from_slice(&cell.modifier.bits().to_le_bytes());
+        }
+        self.file.write_all(&frame)?;
+        // Flushed per frame so an interrupted session still plays back up to its end
+        self.file.flush()
+    }
+}
+
+/// One recorded frame, positioned at the origin
+pub struct RecordedFrame {
+    pub timestamp_ms: u64,
+    pub buffer: Buffer,
+}
+
+pub fn read_frames(path: &str) -> std::io::Result<Vec<RecordedFrame>> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path, msg));
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    let mut frames = Vec::new();
+    let mut at = 0;
+    while at < data.len() {
+        let header = data.get(at..at + FRAME_HEADER).ok_or_else(|| invalid(format!("truncated frame {}", frames.len() + 1)))?;
+        if &header[..4] != FRAME_MAGIC {
+            return Err(invalid(format!("frame {} has no frame marker; not a --record file?", frames.len() + 1)));
+        }
+        let timestamp_ms = u64::from_le_bytes(header[4..12].try_into().unwrap());
+        let width = u16::from_le_bytes(header[12..14].try_into().unwrap());
+        let height = u16::from_le_bytes(header[14..16].try_into().unwrap());
+        at += FRAME_HEADER;
+        let size = width as usize * height as usize * CELL_SIZE;
+        let cells = data.get(at..at + size).ok_or_else(|| invalid(format!("truncated frame {}", frames.len() + 1)))?;
+        let mut buffer = Buffer::empty(Rect::new(0, 0, width, height));
+        for (cell, bytes) in buffer.content.iter_mut().zip(cells.chunks_exact(CELL_SIZE)) {
+            let c = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+            match char::from_u32(c) {
+                Some(c) if c != '\0' => cell.set_char(c),
+                _ => cell.set_symbol(""),
+            };
+            cell.fg = decode_color(&bytes[4..8]);
+            cell.bg = decode_color(&bytes[8..12]);
+            cell.modifier = Modifier::from_bits_truncate(u16::from_le_bytes(bytes[12..14].try_into().unwrap()));
+        }
+        at += size;
+        frames.push(RecordedFrame { timestamp_ms, buffer });
+    }
+    Ok(frames)
+}
@@ -0,0 +1,140 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::Entity;
+
+/// First bytes of every ring log, used by --replay to tell it apart from a CSV
+pub const MAGIC: &[u8; 8] = b"DCGMRING";
+const VERSION: u32 = 1;
+/// Metadata block at the start of the file; records follow it
+const HEADER_SIZE: u64 = 4096;
+/// Fixed part of the header before the newline-separated metric names
+const FIXED_HEADER: usize = 56;
+
+/// Fixed-size binary log: a 4 KB header followed by `capacity` records of
+/// `timestamp_ms: i64, gpu: i32, mig: i32 (-1 for a whole GPU), values: [f32; N]`,
+/// all little-endian. Once full, the oldest record is overwritten.
+pub struct RingWriter {
+    file: File,
+    names: Vec<String>,
+    interval_ms: u64,
+    capacity: u64,
+    /// Slot the next record goes into
+    next: u64,
+    /// Records written since the file was created; the ring holds the last `capacity`
+    written: u64,
+}
+
+fn record_size(fields: usize) -> u64 {
+    16 + 4 * fields as u64
+}
+
+impl RingWriter {
+    /// Creates (or truncates) `path` and sizes it for `capacity` records up front
+    pub fn create(path: &str, names: &[String], interval_ms: u64, capacity: u64) -> std::io::Result<RingWriter> {
+        let capacity = capacity.max(1);
+        if FIXED_HEADER + names.iter().map(|n| n.len() + 1).sum::<usize>() > HEADER_SIZE as usize {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "too many metric names for the ring log header"));
+        }
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        file.set_len(HEADER_SIZE + capacity * record_size(names.len()))?;
+        let mut writer = RingWriter { file, names: names.to_vec(), interval_ms, capacity, next: 0, written: 0 };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.names.len() as u32).to_le_bytes());
+        header.extend_from_slice(&self.interval_ms.to_le_bytes());
+        header.extend_from_slice(&self.capacity.to_le_bytes());
+        header.extend_from_slice(&self.next.to_le_bytes());
+        header.extend_from_slice(&self.written.to_le_bytes());
+        header.extend_from_slice(&(record_size(self.names.len()) as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        for name in &self.names {
+            header.extend_from_slice(name.as_bytes());
+            header.push(b'\n');
+        }
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)
+    }
+
+    pub fn write(&mut self, timestamp_ms: i64, entity: Entity, values: &[f64]) -> std::io::Result<()> {
+        let mut record = Vec::with_capacity(record_size(self.names.len()) as usize);
+        record.extend_from_slice(&timestamp_ms.to_le_bytes());
+        record.extend_from_slice(&(entity.gpu as i32).to_le_bytes());
+        record.extend_from_slice(&entity.instance.map_or(-1, |n| n as i32).to_le_bytes());
+        for i in 0..self.names.len() {
+            record.extend_from_slice(&(values.get(i).copied().unwrap_or(0.0) as f32).to_le_bytes());
+        }
+        self.file.seek(SeekFrom::Start(HEADER_SIZE + self.next * record_size(self.names.len())))?;
+        self.file.write_all(&record)?;
+        self.next = (self.next + 1) % self.capacity;
+        self.written += 1;
+        // The header is rewritten every time so a crash loses at most the newest record
+        self.write_header()
+    }
+}
+
+/// Contents of a ring log, oldest record first
+pub struct RingLog {
+    pub names: Vec<String>,
+    pub records: Vec<(i64, Entity, Vec<f64>)>,
+}
+
+/// Whether `path` starts with the ring log magic bytes
+pub fn is_ring_log(path: &str) -> std::io::Result<bool> {
+    let mut magic = [0u8; 8];
+    let mut file = File::open(path)?;
+    Ok(file.read(&mut magic)? == magic.len() && &magic == MAGIC)
+}
+
+pub fn read_ring_log(path: &str) -> std::io::Result<RingLog> {
+    let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path, msg));
+    let mut file = File::open(path)?;
+    let mut header = vec![0u8; HEADER_SIZE as usize];
+    file.read_exact(&mut header).map_err(|_| invalid("truncated ring log header"))?;
+    if &header[..8] != MAGIC {
+        return Err(invalid("not a ring log"));
+    }
+    let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    let u64_at = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+    if u32_at(8) != VERSION {
+        return Err(invalid("unsupported ring log version"));
+    }
+    let fields = u32_at(12) as usize;
+    let (capacity, next, written) = (u64_at(24), u64_at(32), u64_at(40));
+    let size = u32_at(48) as usize;
+    if capacity == 0 || size < 16 + 4 * fields {
+        return Err(invalid("corrupt ring log header"));
+    }
+    let names: Vec<String> = String::from_utf8_lossy(&header[FIXED_HEADER..])
+        .split('\n')
+        .take(fields)
+        .map(str::to_string)
+        .collect();
+
+    let mut body = Vec::new();
+    file.read_to_end(&mut body)?;
+    let count = written.min(capacity);
+    // Before the first wrap the oldest record is slot 0; after it, the slot about to be overwritten
+    let oldest = if written > capacity { next } else { 0 };
+    let mut records = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let at = ((oldest + i) % capacity) as usize * size;
+        let record = body.get(at..at + size).ok_or_else(|| invalid("truncated ring log"))?;
+        let timestamp = i64::from_le_bytes(record[..8].try_into().unwrap());
+        let gpu = i32::from_le_bytes(record[8..12].try_into().unwrap());
+        let mig = i32::from_le_bytes(record[12..16].try_into().unwrap());
+        let entity = Entity { gpu: gpu.max(0) as usize, instance: usize::try_from(mig).ok() };
+        let values = record[16..16 + 4 * fields]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64)
+            .collect();
+        records.push((timestamp, entity, values));
+    }
+    Ok(RingLog { names, records })
+}
@@ -0,0 +1,118 @@
+use font8x8::{UnicodeFonts, BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, LATIN_FONTS};
+use image::{Rgb, RgbImage};
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+use std::thread;
+
+/// Pixel size of one terminal cell; the 8×8 glyphs are doubled vertically
+const CELL_WIDTH: u32 = 8;
+const CELL_HEIGHT: u32 = 16;
+
+/// Colours used for cells that keep the terminal's own (`Color::Reset`) colours
+#[derive(Clone, Copy)]
+pub struct DefaultColors {
+    pub foreground: Color,
+    pub background: Color,
+}
+
+/// xterm's 16-colour palette, in ANSI order
+const ANSI: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+fn rgb(color: Color, fallback: Color) -> [u8; 3] {
+    let index = match color {
+        Color::Reset => return rgb(fallback, Color::White),
+        Color::Rgb(r, g, b) => return [r, g, b],
+        Color::Indexed(n) => n,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+    };
+    match index {
+        0..=15 => ANSI[index as usize],
+        // 6×6×6 colour cube
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + 40 * n };
+            let n = index - 16;
+            [level(n / 36), level(n / 6 % 6), level(n % 6)]
+        }
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            [gray, gray, gray]
+        }
+    }
+}
+
+/// 8×8 bitmap for the first character of a cell's symbol; unknown characters draw as `?`
+fn glyph(symbol: &str) -> [u8; 8] {
+    let c = symbol.chars().next().unwrap_or(' ');
+    BASIC_FONTS
+        .get(c)
+        .or_else(|| BOX_FONTS.get(c))
+        .or_else(|| BLOCK_FONTS.get(c))
+        .or_else(|| LATIN_FONTS.get(c))
+        .or_else(|| BASIC_FONTS.get('?'))
+        .unwrap_or_default()
+}
+
+/// Draws every cell of `buffer` as an 8×16 pixel block
+pub fn render_png(buffer: &Buffer, colors: DefaultColors) -> RgbImage {
+    let area = buffer.area;
+    let mut image = RgbImage::new(area.width as u32 * CELL_WIDTH, area.height as u32 * CELL_HEIGHT);
+    for y in 0..area.height {
+        for x in 0..area.width {
+            let cell = buffer.get(area.x + x, area.y + y);
+            let (mut fg, mut bg) = (rgb(cell.fg, colors.foreground), rgb(cell.bg, colors.background));
+            if cell.modifier.contains(Modifier::REVERSED) {
+                std::mem::swap(&mut fg, &mut bg);
+            }
+            let rows = glyph(cell.symbol());
+            for py in 0..CELL_HEIGHT {
+                let row = rows[(py / 2) as usize];
+                for px in 0..CELL_WIDTH {
+                    let pixel = if row & (1 << px) != 0 { fg } else { bg };
+                    image.put_pixel(x as u32 * CELL_WIDTH + px, y as u32 * CELL_HEIGHT + py, Rgb(pixel));
+                }
+            }
+        }
+    }
+    image
+}
+
+/// Renders and saves the frame on a background thread so the event loop keeps running;
+/// the handle yields the path once the file is written
+pub fn export_png(buffer: Buffer, colors: DefaultColors, path: String) -> thread::JoinHandle<Result<String, String>> {
+    thread::spawn(move || {
+        render_png(&buffer, colors).save(&path).map_err(|e| e.to_string())?;
+        Ok(path)
+    })
+}
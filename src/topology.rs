@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use crate::discovery::run_with_timeout;
+
+/// Per-direction bandwidth of one NVLink 3 link in bytes/s, used to turn live
+/// NVLTX/NVLRX rates into a utilisation estimate
+pub const NVLINK_LINK_BYTES_PER_SEC: f64 = 25e9;
+
+/// How one GPU reaches another, as reported by `dcgmi topo`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Link {
+    NvLink(u32),
+    /// Any non-NVLink path, e.g. "Connected via a PCIe host bridge"
+    Other(String),
+}
+
+/// Adjacency of the monitored GPUs
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    pub links: BTreeMap<(usize, usize), Link>,
+}
+
+impl Topology {
+    /// Total NVLink links leaving `gpu`
+    pub fn nvlinks_of(&self, gpu: usize) -> u32 {
+        self.links.iter()
+            .filter(|((from, _), _)| *from == gpu)
+            .map(|(_, link)| match link {
+                Link::NvLink(n) => *n,
+                Link::Other(_) => 0,
+            })
+            .sum()
+    }
+
+    /// Every GPU pair connected by NVLink with `links` links; stands in for `dcgmi topo` under --mock
+    pub fn full_mesh(gpus: &[usize], links: u32) -> Topology {
+        let mut topology = Topology::default();
+        for &a in gpus {
+            for &b in gpus.iter().filter(|&&b| b != a) {
+                topology.links.insert((a, b), Link::NvLink(links));
+            }
+        }
+        topology
+    }
+}
+
+const NUMBER_WORDS: [&str; 18] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    "ten", "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen",
+];
+
+/// Reads the link count out of "Connected via twelve NVLINKs" / "Connected via 12 NVLINKs"
+fn parse_link(description: &str) -> Link {
+    let lower = description.to_ascii_lowercase();
+    if lower.contains("nvlink") {
+        let count = lower.split_whitespace().find_map(|word| {
+            word.parse().ok().or_else(|| NUMBER_WORDS.iter().position(|w| *w == word).map(|i| i as u32 + 1))
+        });
+        if let Some(count) = count {
+            return Link::NvLink(count);
+        }
+    }
+    Link::Other(description.to_string())
+}
+
+/// Adds the `| To GPU n | ... |` rows of one `dcgmi topo --gpuid <gpu>` table to `topology`
+pub fn parse_gpu_topology(gpu: usize, output: &str, topology: &mut Topology) {
+    for line in output.lines() {
+        let cells: Vec<&str> = line.split('|').map(str::trim).filter(|c| !c.is_empty()).collect();
+        let [label, description] = cells[..] else {
+            continue;
+        };
+        let Some(peer) = label.strip_prefix("To GPU").and_then(|id| id.trim().parse().ok()) else {
+            continue;
+        };
+        topology.links.insert((gpu, peer), parse_link(description));
+    }
+}
+
+/// Runs `dcgmi topo --gpuid` once per GPU, giving up on one that hangs
+pub fn query_topology(gpus: &[usize]) -> Result<Topology, String> {
+    let mut topology = Topology::default();
+    for &gpu in gpus {
+        let output = run_with_timeout("dcgmi", &["topo", "--gpuid", &gpu.to_string()])?;
+        parse_gpu_topology(gpu, &output, &mut topology);
+    }
+    Ok(topology)
+}
@@ -0,0 +1,16 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn config_keys_satisfy_cli_requires() {
+    let path = std::env::temp_dir().join(format!("dcgmi_tui_config_{}.json", std::process::id()));
+    fs::write(&path, r#"{"alert": ["SMACT>0.5"]}"#).unwrap();
+    // --trigger-cmd requires --alert, which only the config file gives; --field-doc exits before sampling
+    let output = Command::new(env!("CARGO_BIN_EXE_dcgmi_tui"))
+        .args(["--mock", "--field-doc", "--trigger-cmd", "echo hi", "--config"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).ok();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
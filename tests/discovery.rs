@@ -0,0 +1,39 @@
+use dcgm_tui::discovery::{parse_discovery, parse_max_clocks, parse_processes, parse_total_memory, GpuProcess};
+
+#[test]
+fn parses_gpu_names_from_discovery_table() {
+    let output = "\
++-------------------+--------------------------------------------------------------------+
+| GPU ID | Device Information                                                        |
++--------+---------------------------------------------------------------------------+
+| 0      | Name: NVIDIA A100-SXM4-80GB                                               |
+|        | PCI Bus ID: 00000000:07:00.0                                              |
+| 1      | Name: NVIDIA A100-SXM4-80GB                                               |
++--------+---------------------------------------------------------------------------+";
+    let names = parse_discovery(output);
+    assert_eq!(names.len(), 2);
+    assert_eq!(names[&1], "NVIDIA A100-SXM4-80GB");
+}
+
+#[test]
+fn takes_the_highest_rated_clocks() {
+    assert_eq!(parse_max_clocks("1980, 1593\n1755, 1215\n"), Some((1980.0, 1593.0)));
+    assert_eq!(parse_max_clocks("[N/A], [N/A]\n"), None);
+    assert_eq!(parse_max_clocks(""), None);
+}
+
+#[test]
+fn parses_total_memory_per_gpu() {
+    let memory = parse_total_memory("0, 81920\n1, 40960\n2, [N/A]\n");
+    assert_eq!(memory.len(), 2);
+    assert_eq!(memory[&0], 81920.0);
+    assert_eq!(memory[&1], 40960.0);
+}
+
+#[test]
+fn parses_process_rows_in_either_layout() {
+    let output = "+-------+---------+--------+\n| PID   | Name    | Memory |\n| 4242  | python  | 2048 MiB |\n+-------+---------+--------+\n77 ./my app 512 MiB\n9 noise\n";
+    let python = GpuProcess { pid: 4242, name: "python".to_string(), memory_mb: 2048.0 };
+    let app = GpuProcess { pid: 77, name: "./my app".to_string(), memory_mb: 512.0 };
+    assert_eq!(parse_processes(output), vec![python, app]);
+}
@@ -0,0 +1,179 @@
+use dcgm_tui::logging::{parse_delimiter, spawn_logger_thread, split_csv_line, LogDir, LogOptions, OutputFormat, Rotation, TimestampFormat};
+use dcgm_tui::Entity;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Fresh, empty directory for one test's files
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dcgmi_tui_{}_{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn rotation(path: &str, max_bytes: u64, keep: usize) -> Rotation {
+    Rotation { path: path.to_string(), max_bytes, keep }
+}
+
+/// Comma-separated CSV with a header; tests override the fields they exercise
+fn csv_options() -> LogOptions {
+    LogOptions {
+        format: OutputFormat::Csv,
+        timestamp: TimestampFormat::UnixMs,
+        label: None,
+        session_id: None,
+        delimiter: ',',
+        header: true,
+        row_group_size: 1,
+        rotation: None,
+        aggregate_period: None,
+    }
+}
+
+#[test]
+fn rotated_paths_keep_the_extension() {
+    assert_eq!(rotation("logs/out.csv", 1, 3).rotated_path(1), "logs/out.1.csv");
+    assert_eq!(rotation("out.jsonl", 1, 3).rotated_path(2), "out.2.jsonl");
+    assert_eq!(rotation("out", 1, 3).rotated_path(1), "out.1");
+}
+
+#[test]
+fn rotation_keeps_only_the_newest_files_with_headers() {
+    let dir = scratch_dir("rotate");
+    let path = dir.join("out.csv").to_string_lossy().into_owned();
+    let options = LogOptions { rotation: Some(rotation(&path, 200, 2)), ..csv_options() };
+    let logger = spawn_logger_thread(File::create(&path).unwrap(), vec!["SMACT".to_string()], true, options).unwrap();
+    for i in 0..50 {
+        logger.send((Entity::gpu(0), vec![i as f64]));
+    }
+    logger.finish();
+
+    let mut files: Vec<String> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    files.sort();
+    assert_eq!(files, ["out.1.csv", "out.2.csv", "out.csv"]);
+    for file in &files {
+        let contents = fs::read_to_string(dir.join(file)).unwrap();
+        assert!(contents.starts_with("timestamp,gpu,SMACT,annotation\n"), "{} has no header", file);
+    }
+    // The last sample always lands in the live file or the rotation just made
+    let newest = fs::read_to_string(&path).unwrap() + &fs::read_to_string(dir.join("out.1.csv")).unwrap();
+    assert!(newest.contains(",0,49,"));
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn label_and_session_columns_precede_the_annotation() {
+    let dir = scratch_dir("session");
+    let path = dir.join("out.csv");
+    let options = LogOptions { label: Some("run, 1".to_string()), session_id: Some("abc".to_string()), ..csv_options() };
+    let logger = spawn_logger_thread(File::create(&path).unwrap(), vec!["SMACT".to_string()], true, options).unwrap();
+    logger.send((Entity::gpu(0), vec![0.5]));
+    logger.finish();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some("timestamp,gpu,SMACT,label,session_id,annotation"));
+    assert!(lines.next().unwrap().ends_with(",0,0.5,\"run, 1\",abc,"));
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn semicolon_rows_quote_cells_holding_the_delimiter() {
+    assert_eq!(parse_delimiter("semicolon"), Ok(';'));
+    assert_eq!(parse_delimiter("tab"), Ok('\t'));
+    assert!(parse_delimiter(".").is_err());
+    assert!(parse_delimiter("::").is_err());
+
+    let dir = scratch_dir("delimiter");
+    let path = dir.join("out.csv");
+    let options = LogOptions { label: Some("a;b".to_string()), delimiter: ';', ..csv_options() };
+    let logger = spawn_logger_thread(File::create(&path).unwrap(), vec!["SMACT".to_string(), "TEMP".to_string()], true, options).unwrap();
+    logger.send((Entity::gpu(1), vec![0.5, 40.0]));
+    logger.finish();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some("timestamp;gpu;SMACT;TEMP;label;annotation"));
+    let row = split_csv_line(lines.next().unwrap(), ';');
+    assert_eq!(row[1..], ["1", "0.5", "40", "a;b", ""]);
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn header_quotes_aliases_holding_the_delimiter() {
+    let dir = scratch_dir("alias_delimiter");
+    let names = vec!["a;b".to_string(), "TEMP".to_string()];
+    let path = dir.join("out.csv");
+    let logger = spawn_logger_thread(File::create(&path).unwrap(), names.clone(), true, LogOptions { delimiter: ';', ..csv_options() }).unwrap();
+    logger.send((Entity::gpu(0), vec![0.5, 40.0]));
+    logger.finish();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let mut lines = contents.lines().map(|line| split_csv_line(line, ';'));
+    let header = lines.next().unwrap();
+    assert_eq!(header[..4], ["timestamp", "gpu", "a;b", "TEMP"]);
+    assert_eq!(header.len(), lines.next().unwrap().len());
+
+    let path = dir.join("aggregate.csv");
+    let options = LogOptions { delimiter: ';', aggregate_period: Some(Duration::from_secs(60)), ..csv_options() };
+    let logger = spawn_logger_thread(File::create(&path).unwrap(), names, true, options).unwrap();
+    logger.send((Entity::gpu(0), vec![0.5, 40.0]));
+    logger.finish();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let mut lines = contents.lines().map(|line| split_csv_line(line, ';'));
+    let header = lines.next().unwrap();
+    assert_eq!(header[4], "a;b_mean");
+    assert_eq!(header.len(), lines.next().unwrap().len());
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn log_dir_writes_one_file_per_gpu() {
+    let dir = scratch_dir("log_dir");
+    let path = dir.join("logs").to_string_lossy().into_owned();
+    let mut logs = LogDir::create(&path, "run".to_string(), vec!["SMACT".to_string()], csv_options()).unwrap();
+    logs.send((Entity::gpu(0), vec![0.1])).unwrap();
+    logs.send((Entity::gpu(1), vec![0.2])).unwrap();
+    logs.send((Entity { gpu: 1, instance: Some(0) }, vec![0.3])).unwrap();
+    assert_eq!(logs.path(1), dir.join("logs").join("gpu_1_run.csv"));
+    logs.finish();
+
+    let gpu0 = fs::read_to_string(dir.join("logs/gpu_0_run.csv")).unwrap();
+    let gpu1 = fs::read_to_string(dir.join("logs/gpu_1_run.csv")).unwrap();
+    assert_eq!(gpu0.lines().count(), 2);
+    assert!(gpu1.starts_with("timestamp,gpu,SMACT,annotation\n"));
+    assert!(gpu1.contains(",1,0.2,") && gpu1.contains(",1/0,0.3,"));
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn aggregate_period_writes_one_stats_row_per_gpu() {
+    let dir = scratch_dir("aggregate");
+    let path = dir.join("out.csv");
+    let options = LogOptions { aggregate_period: Some(Duration::from_secs(60)), ..csv_options() };
+    let logs = LogDir::create(&dir.join("logs").to_string_lossy(), "run".to_string(), vec!["SMACT".to_string()], options.clone()).unwrap();
+    assert!(!logs.supports_annotations());
+    let logger = spawn_logger_thread(File::create(&path).unwrap(), vec!["SMACT".to_string()], true, options).unwrap();
+    assert!(!logger.supports_annotations);
+    for value in [0.1, 0.2, 0.3, 0.4, 0.5] {
+        logger.send((Entity::gpu(0), vec![value]));
+    }
+    logger.send((Entity::gpu(1), vec![40.0]));
+    // Finishing closes the open window, so the rows appear without waiting a minute
+    logger.finish();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some("window_start,window_end,gpu,samples,SMACT_mean,SMACT_p50,SMACT_p90,SMACT_p99,SMACT_min,SMACT_max"));
+    let gpu0 = split_csv_line(lines.next().unwrap(), ',');
+    assert_eq!(gpu0[2..4], ["0", "5"]);
+    let stats: Vec<f64> = gpu0[4..].iter().map(|cell| cell.parse().unwrap()).collect();
+    for (stat, expected) in stats.iter().zip([0.3, 0.3, 0.46, 0.496, 0.1, 0.5]) {
+        assert!((stat - expected).abs() < 1e-9, "{:?}", stats);
+    }
+    assert_eq!(split_csv_line(lines.next().unwrap(), ',')[2..], ["1", "1", "40", "40", "40", "40", "40", "40"]);
+    assert_eq!(lines.next(), None);
+    fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,116 @@
+use dcgm_tui::{format_bytes_with_unit, format_compact_value, parse_metric_line, pearson, percentile, watchface_label, Entity, MetricKind, CLOCK_FIELDS, DEFAULT_FIELDS, FIELD_DOCS};
+
+#[test]
+fn parses_gpu_line() {
+    let (entity, values) = parse_metric_line("GPU 3    0.120  0.400  1024", 3).unwrap();
+    assert_eq!(entity, Entity::gpu(3));
+    assert_eq!(values, vec![0.12, 0.4, 1024.0]);
+}
+
+#[test]
+fn parses_mig_instance_line() {
+    let (entity, values) = parse_metric_line("GPU 0/1 0.5 0.25", 2).unwrap();
+    assert_eq!(entity, Entity { gpu: 0, instance: Some(1) });
+    assert_eq!(values, vec![0.5, 0.25]);
+}
+
+#[test]
+fn rejects_malformed_lines() {
+    // dmon headers, units rows and blank lines
+    assert!(parse_metric_line("#Entity   SMACT  SMOCC", 2).is_none());
+    assert!(parse_metric_line("ID", 2).is_none());
+    assert!(parse_metric_line("", 2).is_none());
+    // Bad entity ids
+    assert!(parse_metric_line("GPU x 0.1 0.2", 2).is_none());
+    assert!(parse_metric_line("GPU", 0).is_none());
+    // Wrong number of values, including unsupported fields printed as N/A
+    assert!(parse_metric_line("GPU 0 0.1", 2).is_none());
+    assert!(parse_metric_line("GPU 0 0.1 0.2 0.3", 2).is_none());
+    assert!(parse_metric_line("GPU 0 0.1 N/A", 2).is_none());
+}
+
+#[test]
+fn percentile_of_empty_slice_is_zero() {
+    assert_eq!(percentile(&[], 50.0), 0.0);
+    assert_eq!(percentile(&[], 99.0), 0.0);
+}
+
+#[test]
+fn percentile_of_single_value() {
+    for pct in [0.0, 50.0, 90.0, 100.0] {
+        assert_eq!(percentile(&[7.0], pct), 7.0);
+    }
+}
+
+#[test]
+fn percentile_of_odd_slice() {
+    let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+    assert_eq!(percentile(&sorted, 0.0), 1.0);
+    assert_eq!(percentile(&sorted, 50.0), 3.0);
+    assert_eq!(percentile(&sorted, 100.0), 5.0);
+    assert!((percentile(&sorted, 90.0) - 4.6).abs() < 1e-9);
+}
+
+#[test]
+fn percentile_of_even_slice_interpolates() {
+    let sorted = [10.0, 20.0, 30.0, 40.0];
+    assert_eq!(percentile(&sorted, 50.0), 25.0);
+    assert_eq!(percentile(&sorted, 0.0), 10.0);
+    assert_eq!(percentile(&sorted, 100.0), 40.0);
+}
+
+#[test]
+fn formats_every_byte_unit() {
+    const KB: f64 = 1024.0;
+    assert_eq!(format_bytes_with_unit(0.0, false), "0 B");
+    assert_eq!(format_bytes_with_unit(512.0, false), "512 B");
+    assert_eq!(format_bytes_with_unit(1.5 * KB, false), "1.50 KB");
+    assert_eq!(format_bytes_with_unit(2.0 * KB * KB, false), "2.00 MB");
+    assert_eq!(format_bytes_with_unit(3.25 * KB * KB * KB, false), "3.25 GB");
+    assert_eq!(format_bytes_with_unit(4.0 * KB * KB * KB * KB, false), "4.00 TB");
+    // TB is the largest unit
+    assert_eq!(format_bytes_with_unit(2048.0 * KB * KB * KB * KB, false), "2048.00 TB");
+}
+
+#[test]
+fn formats_rates_per_second() {
+    assert_eq!(format_bytes_with_unit(100.0, true), "100 B/s");
+    assert_eq!(format_bytes_with_unit(8e9, true), "7.45 GB/s");
+}
+
+#[test]
+fn compact_values_drop_spaces_and_decimals() {
+    assert_eq!(format_compact_value(MetricKind::Ratio, 0.874), "87%");
+    assert_eq!(format_compact_value(MetricKind::BytesPerSec, 8.3 * 1024.0 * 1024.0 * 1024.0), "8.3GB/s");
+    assert_eq!(format_compact_value(MetricKind::BytesPerSec, 12.0), "12B/s");
+    assert_eq!(format_compact_value(MetricKind::Megabytes, 40960.0), "40.0GB");
+    assert_eq!(format_compact_value(MetricKind::Celsius, 71.4), "71°C");
+    assert_eq!(format_compact_value(MetricKind::Watts, 249.6), "250W");
+}
+
+#[test]
+fn watchface_labels_follow_combined_columns_and_aliases() {
+    assert_eq!(watchface_label(1011, "NVLTX", false, false), "NVL↑");
+    assert_eq!(watchface_label(1011, "NVLBD", true, false), "NVL⇅");
+    assert_eq!(watchface_label(1009, "PCIBD", true, false), "PCIe⇅");
+    // An aliased sum keeps the user's name rather than falling back to the TX label
+    assert_eq!(watchface_label(1011, "nvlink", true, true), "nvlink");
+    // Aliasing another metric to NVLBD does not make it look like the NVLink sum
+    assert_eq!(watchface_label(1002, "NVLBD", false, true), "NVLBD");
+}
+
+#[test]
+fn pearson_is_signed_and_nan_for_flat_series() {
+    let rising = [1.0, 2.0, 3.0, 4.0];
+    assert!((pearson(&rising, &[2.0, 4.0, 6.0, 8.0]) - 1.0).abs() < 1e-9);
+    assert!((pearson(&rising, &[8.0, 6.0, 4.0, 2.0]) + 1.0).abs() < 1e-9);
+    assert!(pearson(&rising, &[5.0; 4]).is_nan());
+    assert!(pearson(&[1.0], &[2.0]).is_nan());
+}
+
+#[test]
+fn every_built_in_field_is_documented() {
+    for (id, name) in DEFAULT_FIELDS.iter().chain(&CLOCK_FIELDS) {
+        assert!(FIELD_DOCS.iter().any(|(doc_id, ..)| doc_id == id), "{} ({}) has no --field-doc entry", name, id);
+    }
+}
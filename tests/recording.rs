@@ -0,0 +1,25 @@
+use dcgm_tui::recording::{read_frames, FrameWriter};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+
+#[test]
+fn frames_round_trip_with_their_styles() {
+    let path = std::env::temp_dir().join(format!("dcgmi_tui_record_{}.bin", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let mut first = Buffer::empty(Rect::new(0, 0, 6, 2));
+    first.set_string(0, 0, "SMACT█", Style::default().fg(Color::Rgb(1, 2, 3)).bg(Color::Indexed(42)).add_modifier(Modifier::BOLD));
+    let second = Buffer::empty(Rect::new(0, 0, 3, 1));
+    let mut writer = FrameWriter::create(&path).unwrap();
+    writer.write(1_000, &first).unwrap();
+    writer.write(1_250, &second).unwrap();
+    drop(writer);
+
+    let frames = read_frames(&path).unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].timestamp_ms, 1_000);
+    assert_eq!(frames[0].buffer, first);
+    assert_eq!(frames[1].timestamp_ms, 1_250);
+    assert_eq!(frames[1].buffer.area, Rect::new(0, 0, 3, 1));
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,42 @@
+use dcgm_tui::ringlog::{is_ring_log, read_ring_log, RingWriter};
+use dcgm_tui::Entity;
+
+#[test]
+fn wrapped_ring_returns_the_newest_records_oldest_first() {
+    let path = std::env::temp_dir().join(format!("dcgmi_tui_ring_{}.bin", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let names = vec!["SMACT".to_string(), "TEMP".to_string()];
+    let mut writer = RingWriter::create(&path, &names, 100, 4).unwrap();
+    // Six records into four slots: the first two are overwritten; odd ones are MIG instance 1
+    for i in 0..6 {
+        let entity = if i % 2 == 1 { Entity { gpu: 0, instance: Some(1) } } else { Entity::gpu(0) };
+        writer.write(1_000 + i, entity, &[i as f64 * 0.25, 40.0 + i as f64]).unwrap();
+    }
+    drop(writer);
+
+    assert!(is_ring_log(&path).unwrap());
+    let log = read_ring_log(&path).unwrap();
+    assert_eq!(log.names, names);
+    let expected: Vec<(i64, Entity, Vec<f64>)> = (2..6)
+        .map(|i| {
+            let entity = if i % 2 == 1 { Entity { gpu: 0, instance: Some(1) } } else { Entity::gpu(0) };
+            (1_000 + i, entity, vec![i as f64 * 0.25, 40.0 + i as f64])
+        })
+        .collect();
+    assert_eq!(log.records, expected);
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn partly_filled_ring_starts_at_the_first_slot() {
+    let path = std::env::temp_dir().join(format!("dcgmi_tui_ring_partial_{}.bin", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let mut writer = RingWriter::create(&path, &["SMACT".to_string()], 100, 4).unwrap();
+    writer.write(1_000, Entity::gpu(1), &[0.5]).unwrap();
+    writer.write(1_100, Entity::gpu(2), &[0.75]).unwrap();
+    drop(writer);
+
+    let log = read_ring_log(&path).unwrap();
+    assert_eq!(log.records, vec![(1_000, Entity::gpu(1), vec![0.5]), (1_100, Entity::gpu(2), vec![0.75])]);
+    std::fs::remove_file(&path).ok();
+}